@@ -0,0 +1,255 @@
+use std::{
+    mem::{self, MaybeUninit},
+    ptr::{self, NonNull},
+};
+
+use crate::{dispose_node, set_parent, Link, Node};
+
+/// Number of nodes in a pool's first chunk. Every chunk after that is twice as big as the last.
+const FIRST_CHUNK_LEN: usize = 32;
+
+/// Hands out memory for a tree's nodes, either one at a time from the global allocator,
+/// or from a pool of large chunks that are only given back all at once.
+pub(crate) struct NodeAlloc<T> {
+    pool: Option<Pool<T>>,
+    /// ID to give the next node allocated.
+    #[cfg(feature = "node-ids")]
+    next_id: u64,
+}
+
+struct Pool<T> {
+    chunks: Vec<NonNull<[MaybeUninit<Node<T>>]>>,
+    /// Number of slots handed out from the last chunk.
+    used: usize,
+    /// Slots that were handed out and then freed, ready for reuse.
+    free: Vec<NonNull<Node<T>>>,
+}
+
+unsafe impl<#[may_dangle] T> Drop for Pool<T> {
+    fn drop(&mut self) {
+        for chunk in self.chunks.drain(..) {
+            // Safety: chunks are created from boxes, and their slots only ever hold
+            // nodes that have already been dropped or moved out, so we just free memory.
+            let _ = unsafe { Box::from_raw(chunk.as_ptr()) };
+        }
+    }
+}
+
+impl<T> Pool<T> {
+    fn new() -> Self {
+        Self {
+            chunks: Vec::new(),
+            used: 0,
+            free: Vec::new(),
+        }
+    }
+
+    fn slot(&mut self) -> NonNull<Node<T>> {
+        if let Some(slot) = self.free.pop() {
+            return slot;
+        }
+
+        let full = self
+            .chunks
+            .last()
+            .is_none_or(|chunk| self.used == chunk.len());
+        if full {
+            let len = self
+                .chunks
+                .last()
+                .map_or(FIRST_CHUNK_LEN, |chunk| chunk.len() * 2);
+            let chunk = Box::<[Node<T>]>::new_uninit_slice(len);
+            // Safety: Box::into_raw is never null.
+            self.chunks
+                .push(unsafe { NonNull::new_unchecked(Box::into_raw(chunk)) });
+            self.used = 0;
+        }
+
+        // Safety: we just made sure there is an unused slot left in the last chunk.
+        unsafe {
+            let chunk = self.chunks.last().unwrap_unchecked();
+            let slot = chunk.cast::<Node<T>>().add(self.used);
+            self.used += 1;
+            slot
+        }
+    }
+}
+
+impl<T> NodeAlloc<T> {
+    pub(crate) fn global() -> Self {
+        Self {
+            pool: None,
+            #[cfg(feature = "node-ids")]
+            next_id: 0,
+        }
+    }
+
+    pub(crate) fn pooled() -> Self {
+        Self {
+            pool: Some(Pool::new()),
+            #[cfg(feature = "node-ids")]
+            next_id: 0,
+        }
+    }
+
+    /// An empty allocator of the same kind as this one.
+    pub(crate) fn new_like(&self) -> Self {
+        match self.pool {
+            None => Self::global(),
+            Some(_) => Self::pooled(),
+        }
+    }
+
+    /// Take ownership of a detached subtree whose nodes came from another allocator,
+    /// returning its new root. Globally allocated nodes can be shared as they are,
+    /// but pooled ones have to be moved into nodes of our own, keeping the same shape.
+    /// Safety: root must be `None` or a valid, detached tree whose nodes all came from `from`.
+    pub(crate) unsafe fn adopt(&mut self, from: &mut NodeAlloc<T>, root: Link<T>) -> Link<T> {
+        if self.pool.is_none() && from.pool.is_none() {
+            return root;
+        }
+
+        let old = root?;
+        // Safety: old is a valid node from `from`, and its children are adopted before it is freed.
+        unsafe {
+            let (left, right, size) = (old.as_ref().left, old.as_ref().right, old.as_ref().size);
+            #[cfg(feature = "node-ids")]
+            let id = old.as_ref().id;
+            let left = self.adopt(from, left);
+            let right = self.adopt(from, right);
+            let mut new = self.alloc(from.free(old));
+            let new_ref = new.as_mut();
+            #[cfg(feature = "node-ids")]
+            {
+                new_ref.id = id;
+            }
+            new_ref.left = left;
+            new_ref.right = right;
+            new_ref.size = size;
+            set_parent(left, Some(new));
+            set_parent(right, Some(new));
+            Some(new)
+        }
+    }
+
+    /// Turn the allocator into a raw pointer to its pool, which is null if it isn't pooled.
+    pub(crate) fn into_raw(self) -> *mut () {
+        match self.pool {
+            None => ptr::null_mut(),
+            Some(pool) => Box::into_raw(Box::new(pool)).cast(),
+        }
+    }
+
+    /// Rebuild an allocator from a pointer produced by `into_raw`.
+    /// Safety: pool must have come from `into_raw` on a `NodeAlloc<T>`, and must not be used again.
+    pub(crate) unsafe fn from_raw(pool: *mut ()) -> Self {
+        Self {
+            pool: (!pool.is_null()).then(|| *unsafe { Box::from_raw(pool.cast::<Pool<T>>()) }),
+            #[cfg(feature = "node-ids")]
+            next_id: 0,
+        }
+    }
+
+    /// Carry on numbering nodes after the highest ID already in a tree, for allocators that
+    /// had their count thrown away by `into_raw`.
+    /// Safety: root must be `None` or a valid tree.
+    #[cfg(feature = "node-ids")]
+    pub(crate) unsafe fn resume_ids(&mut self, root: Link<T>) {
+        unsafe fn max_id<T>(link: Link<T>) -> Option<u64> {
+            // Safety: guaranteed by the caller.
+            let node = unsafe { link?.as_ref() };
+            let children = unsafe { max_id(node.left).max(max_id(node.right)) };
+            Some(children.map_or(node.id, |id| id.max(node.id)))
+        }
+        // Safety: guaranteed by the caller.
+        self.next_id = unsafe { max_id(root) }.map_or(0, |id| id + 1);
+    }
+
+    /// Allocate a new, unlinked node.
+    pub(crate) fn alloc(&mut self, item: T) -> NonNull<Node<T>> {
+        #[allow(unused_mut)]
+        let mut node = Node::new(item);
+        #[cfg(feature = "node-ids")]
+        {
+            node.id = self.next_id;
+            self.next_id += 1;
+        }
+        match &mut self.pool {
+            // Safety: Box::into_raw is never null.
+            None => unsafe { NonNull::new_unchecked(Box::into_raw(Box::new(node))) },
+            Some(pool) => {
+                let slot = pool.slot();
+                // Safety: the slot is unused, and big enough for a node.
+                unsafe { slot.write(node) };
+                slot
+            }
+        }
+    }
+
+    /// Deallocate a node, handing back its item.
+    /// Safety: node must have come from this allocator, and must not be used afterwards.
+    pub(crate) unsafe fn free(&mut self, node: NonNull<Node<T>>) -> T {
+        match &mut self.pool {
+            // Safety: node came from a Box, and is not used again.
+            None => unsafe { Box::from_raw(node.as_ptr()) }.item,
+            Some(pool) => {
+                // Safety: node is valid, and the slot is not read again until it is reused.
+                let item = unsafe { ptr::addr_of!((*node.as_ptr()).item).read() };
+                pool.free.push(node);
+                item
+            }
+        }
+    }
+
+    /// Drop every node of a detached subtree, while the rest of the tree lives on.
+    /// Safety: root must be `None` or a valid, detached tree whose nodes came from this allocator,
+    /// none of which are used afterwards.
+    pub(crate) unsafe fn free_tree(&mut self, root: Link<T>) {
+        let Some(node) = root else { return };
+        if self.pool.is_none() {
+            unsafe { dispose_node(node) };
+            return;
+        }
+        // Safety: each node is freed after its children, and never used again.
+        unsafe {
+            let (left, right) = (node.as_ref().left, node.as_ref().right);
+            self.free_tree(left);
+            self.free_tree(right);
+            self.free(node);
+        }
+    }
+
+    /// Drop every node of the tree that owns this allocator.
+    /// Pooled nodes are not given back one by one; their chunks are freed all at once instead.
+    /// Safety: root must be `None` or a tree whose nodes all came from this allocator,
+    /// none of which are used afterwards.
+    pub(crate) unsafe fn dispose(&mut self, root: Link<T>) {
+        match &mut self.pool {
+            None => {
+                if let Some(root) = root {
+                    unsafe { dispose_node(root) };
+                }
+            }
+            Some(pool) => {
+                if mem::needs_drop::<T>() {
+                    unsafe { drop_items(root) };
+                }
+                *pool = Pool::new();
+            }
+        }
+    }
+}
+
+/// Drop the items of a tree in place, without freeing its nodes.
+/// Safety: root must be `None` or a valid tree, whose items are not used afterwards.
+unsafe fn drop_items<T>(root: Link<T>) {
+    if let Some(node) = root {
+        // Safety: the node is valid, and its item is dropped exactly once.
+        unsafe {
+            let node = node.as_ptr();
+            drop_items((*node).left);
+            drop_items((*node).right);
+            ptr::drop_in_place(&mut (*node).item);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::{borrow::Borrow, ops::Deref};
+
+use crate::BinarySearchTree;
+
+/// What a `BoundedTree` does when an item is inserted while it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Turn the new item away.
+    RejectNew,
+    /// Throw away the smallest item, which keeps the largest ones.
+    EvictMin,
+    /// Throw away the largest item, which keeps the smallest ones.
+    EvictMax,
+}
+
+/// A tree that never holds more than a fixed number of items.
+/// It can be read like the tree it wraps, but changes have to go through its own methods.
+pub struct BoundedTree<T> {
+    tree: BinarySearchTree<T>,
+    capacity: usize,
+    policy: EvictionPolicy,
+}
+
+impl<T> Deref for BoundedTree<T> {
+    type Target = BinarySearchTree<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T> BoundedTree<T> {
+    pub fn new(capacity: usize, policy: EvictionPolicy) -> Self {
+        Self {
+            tree: BinarySearchTree::new(),
+            capacity,
+            policy,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn policy(&self) -> EvictionPolicy {
+        self.policy
+    }
+
+    pub fn into_inner(self) -> BinarySearchTree<T> {
+        self.tree
+    }
+
+    /// Drop every item, making room for `capacity` new ones.
+    pub fn clear(&mut self) {
+        self.tree.clear();
+    }
+}
+
+impl<T: Ord> BoundedTree<T> {
+    /// Insert an item, applying the eviction policy if the tree is full.
+    /// Returns the item that didn't fit, which is either the new one or an evicted one.
+    pub fn insert(&mut self, value: T) -> Option<T> {
+        if self.tree.len() < self.capacity {
+            self.tree.insert(value);
+            return None;
+        }
+
+        match self.policy {
+            EvictionPolicy::RejectNew => Some(value),
+            EvictionPolicy::EvictMin => {
+                if self.tree.min().is_none_or(|min| value <= *min) {
+                    return Some(value);
+                }
+                let evicted = self.tree.pop_first();
+                self.tree.insert(value);
+                evicted
+            }
+            EvictionPolicy::EvictMax => {
+                if self.tree.max().is_none_or(|max| value >= *max) {
+                    return Some(value);
+                }
+                let evicted = self.tree.pop_last();
+                self.tree.insert(value);
+                evicted
+            }
+        }
+    }
+
+    pub fn delete(&mut self, item: &T) {
+        self.tree.delete(item);
+    }
+
+    /// Remove an item equal to `key`, handing it back if there was one.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.tree.remove(key)
+    }
+
+    /// The same as `remove`, under the name `BTreeSet` uses.
+    pub fn take<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove(key)
+    }
+}
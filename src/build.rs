@@ -0,0 +1,248 @@
+use std::{cmp::Ordering, error::Error, fmt, io::BufRead, ptr::NonNull};
+
+use crate::{alloc::NodeAlloc, set_parent, BinarySearchTree, DuplicatePolicy, Link, Node};
+
+/// Builds a balanced tree out of items that arrive one at a time in sorted order,
+/// without knowing up front how many there will be.
+///
+/// Numbering items from 1, item `i` goes where it would sit in an infinite perfect tree
+/// numbered in order, i.e. at height `i.trailing_zeros()`. Its left child is then the last
+/// item seen one level down, and its parent is either the last item seen one level up,
+/// or one that is still to come. So only one node per level has to be kept track of.
+struct SortedBuilder<T> {
+    nodes: NodeAlloc<T>,
+    count: usize,
+    last: Link<T>,
+    /// Per height, the latest node whose parent hasn't arrived yet, and its number.
+    orphans: Vec<Option<(usize, NonNull<Node<T>>)>>,
+    /// Per height, the latest node whose right child hasn't arrived yet.
+    open: Vec<Link<T>>,
+}
+
+impl<T> SortedBuilder<T>
+where
+    T: Ord,
+{
+    fn new(nodes: NodeAlloc<T>) -> Self {
+        Self {
+            nodes,
+            count: 0,
+            last: None,
+            orphans: Vec::new(),
+            open: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, item: T) {
+        if let Some(last) = self.last {
+            // Safety: last is a node of the tree we are building.
+            let last = unsafe { &last.as_ref().item };
+            assert!(*last <= item, "items must be in sorted order");
+        }
+
+        self.count += 1;
+        let index = self.count;
+        let height = index.trailing_zeros() as usize;
+        if self.open.len() < height + 2 {
+            self.open.resize(height + 2, None);
+            self.orphans.resize(height + 2, None);
+        }
+
+        let mut node = self.nodes.alloc(item);
+        // Safety: every node we link up was allocated by us, and is part of the tree we are building.
+        unsafe {
+            if height > 0 {
+                let left = self.orphans[height - 1].take().map(|(_, left)| left);
+                node.as_mut().left = left;
+                set_parent(left, Some(node));
+                self.open[height] = Some(node);
+            }
+
+            if (index >> (height + 1)) & 1 == 1 {
+                // The parent came before us, and was waiting for its right child.
+                let mut parent = self.open[height + 1].take().unwrap_unchecked();
+                parent.as_mut().right = Some(node);
+                set_parent(Some(node), Some(parent));
+            } else {
+                self.orphans[height] = Some((index, node));
+            }
+        }
+        self.last = Some(node);
+    }
+}
+
+impl<T> SortedBuilder<T> {
+    /// Link up what's left, handing back the finished tree and leaving the builder empty.
+    fn finish(&mut self) -> BinarySearchTree<T> {
+        let mut root = None;
+        // Safety: as in `push`.
+        unsafe {
+            // Nodes whose parent never arrived hang off their nearest ancestor that did,
+            // whose right child must be missing too. The one without such an ancestor is the root.
+            for (index, node) in self.orphans.iter().flatten().copied() {
+                let mut ancestor = index;
+                loop {
+                    if ancestor.is_power_of_two() {
+                        root = Some(node);
+                        break;
+                    }
+                    let height = ancestor.trailing_zeros();
+                    ancestor = if (ancestor >> (height + 1)) & 1 == 1 {
+                        ancestor - (1 << height)
+                    } else {
+                        ancestor + (1 << height)
+                    };
+                    if ancestor <= self.count {
+                        let height = ancestor.trailing_zeros() as usize;
+                        let mut parent = self.open[height].unwrap_unchecked();
+                        parent.as_mut().right = Some(node);
+                        set_parent(Some(node), Some(parent));
+                        break;
+                    }
+                }
+            }
+            fix_sizes(root);
+        }
+
+        self.count = 0;
+        self.last = None;
+        self.orphans.clear();
+        self.open.clear();
+        let mut tree =
+            BinarySearchTree::with_nodes(std::mem::replace(&mut self.nodes, NodeAlloc::global()));
+        tree.root = root;
+        tree
+    }
+}
+
+impl<T> Drop for SortedBuilder<T> {
+    fn drop(&mut self) {
+        // If building was cut short, the nodes so far still need to be freed.
+        if self.count > 0 {
+            drop(self.finish());
+        }
+    }
+}
+
+/// How sorted input turned out not to be sorted, as found by `try_from_sorted_vec`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortErrorKind {
+    /// An item was less than the one before it.
+    OutOfOrder,
+    /// An item was equal to the one before it, when duplicates are rejected.
+    Duplicate,
+}
+
+/// The first place where supposedly sorted input wasn't.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SortError {
+    /// Index of the item that is out of place, compared with the one before it.
+    pub index: usize,
+    pub kind: SortErrorKind,
+}
+
+impl fmt::Display for SortError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            SortErrorKind::OutOfOrder => {
+                write!(f, "item {} is less than the item before it", self.index)
+            }
+            SortErrorKind::Duplicate => {
+                write!(f, "item {} is equal to the item before it", self.index)
+            }
+        }
+    }
+}
+
+impl Error for SortError {}
+
+/// Recompute the subtree sizes of a whole tree, returning the size of the root.
+/// Safety: l must be `None` or point to a valid tree.
+unsafe fn fix_sizes<T>(l: Link<T>) -> usize {
+    let Some(mut node) = l else { return 0 };
+    // Safety: guaranteed by the caller.
+    unsafe {
+        let node = node.as_mut();
+        node.size = 1 + fix_sizes(node.left) + fix_sizes(node.right);
+        node.size
+    }
+}
+
+impl<T> BinarySearchTree<T> {
+    /// Build a balanced tree from items that are already sorted, as they arrive.
+    /// Apart from the nodes themselves, this only needs memory proportional to the tree's height.
+    ///
+    /// Panics if the items are not in sorted order.
+    pub fn from_sorted_iter<I>(iter: I) -> Self
+    where
+        T: Ord,
+        I: IntoIterator<Item = T>,
+    {
+        let mut builder = SortedBuilder::new(NodeAlloc::global());
+        for item in iter {
+            builder.push(item);
+        }
+        builder.finish()
+    }
+
+    /// Build a balanced tree from a vector, after checking that it is sorted, in O(n) time.
+    /// Returns an error pointing at the first item that is out of order, or that
+    /// duplicates the item before it if `duplicates` is `DuplicatePolicy::Reject`.
+    /// With `DuplicatePolicy::Replace`, only the last of each run of equal items is kept.
+    /// The tree gets `duplicates` as its duplicate policy.
+    pub fn try_from_sorted_vec(
+        mut items: Vec<T>,
+        duplicates: DuplicatePolicy,
+    ) -> Result<Self, SortError>
+    where
+        T: Ord,
+    {
+        for (i, pair) in items.windows(2).enumerate() {
+            let kind = match pair[0].cmp(&pair[1]) {
+                Ordering::Less => continue,
+                Ordering::Equal if duplicates != DuplicatePolicy::Reject => continue,
+                Ordering::Equal => SortErrorKind::Duplicate,
+                Ordering::Greater => SortErrorKind::OutOfOrder,
+            };
+            return Err(SortError { index: i + 1, kind });
+        }
+        if duplicates == DuplicatePolicy::Replace {
+            items.dedup_by(|later, earlier| {
+                let equal = T::cmp(later, earlier) == Ordering::Equal;
+                if equal {
+                    std::mem::swap(later, earlier);
+                }
+                equal
+            });
+        }
+        let mut tree = Self::from_sorted_vec(items);
+        tree.duplicates = duplicates;
+        Ok(tree)
+    }
+
+    /// Build a balanced tree from a sorted stream of lines, such as a large sorted file,
+    /// parsing one item from each line (without its line ending) with `parse`.
+    /// Only one line is held in memory at a time.
+    ///
+    /// Panics if the parsed items are not in sorted order.
+    pub fn from_sorted_reader<R, F, E>(mut reader: R, mut parse: F) -> Result<Self, E>
+    where
+        T: Ord,
+        R: BufRead,
+        F: FnMut(&str) -> Result<T, E>,
+        E: From<std::io::Error>,
+    {
+        let mut builder = SortedBuilder::new(NodeAlloc::global());
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let trimmed = line.strip_suffix('\n').unwrap_or(&line);
+            let trimmed = trimmed.strip_suffix('\r').unwrap_or(trimmed);
+            builder.push(parse(trimmed)?);
+        }
+        Ok(builder.finish())
+    }
+}
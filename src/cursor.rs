@@ -0,0 +1,272 @@
+use std::{borrow::Borrow, marker::PhantomData};
+
+use crate::{
+    count_below, insert_rank_node, remove_rank_node, select_node, BinarySearchTree, Link, Position,
+};
+
+/// A read-only cursor over the items of a `BinarySearchTree`, which can be moved back and
+/// forth in sorted order. Besides pointing at an item, it can point at a "ghost" position
+/// off the ends of the tree, from which moving forwards wraps round to the smallest item,
+/// and moving backwards to the largest.
+pub struct Cursor<'a, T> {
+    root: Link<T>,
+    position: Position<T>,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: as with `Iter`, a cursor only reads items through a shared borrow of the tree.
+unsafe impl<T: Sync> Send for Cursor<'_, T> {}
+unsafe impl<T: Sync> Sync for Cursor<'_, T> {}
+
+impl<T> Clone for Cursor<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root,
+            position: self.position.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Cursor<'a, T> {
+    /// Safety: root must belong to a tree that is borrowed for 'a, and position must be on it.
+    unsafe fn new(root: Link<T>, position: Position<T>) -> Self {
+        Self {
+            root,
+            position,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The item the cursor points at, or `None` at the ghost position.
+    pub fn current(&self) -> Option<&'a T> {
+        // Safety: the tree is borrowed for 'a, so its nodes are alive and not mutated.
+        self.position
+            .node()
+            .map(|node| unsafe { &node.as_ref().item })
+    }
+
+    /// Depth of the current item's node, the root being at depth 0, or `None` at the ghost position.
+    pub fn depth(&self) -> Option<usize> {
+        self.position.node()?;
+        // Safety: as in `current`.
+        Some(unsafe { self.position.depth() })
+    }
+
+    /// Move to the next item in sorted order, or to the ghost position after the largest one.
+    pub fn move_next(&mut self) {
+        // Safety: as in `current`.
+        unsafe {
+            if self.position.node().is_some() {
+                self.position.move_next();
+            } else {
+                self.position = Position::first(self.root);
+            }
+        }
+    }
+
+    /// Move to the previous item in sorted order, or to the ghost position before the smallest one.
+    pub fn move_prev(&mut self) {
+        // Safety: as in `current`.
+        unsafe {
+            if self.position.node().is_some() {
+                self.position.move_prev();
+            } else {
+                self.position = Position::last(self.root);
+            }
+        }
+    }
+
+    /// The item `move_next` would move to, without moving.
+    pub fn peek_next(&self) -> Option<&'a T> {
+        let mut next = self.clone();
+        next.move_next();
+        next.current()
+    }
+
+    /// The item `move_prev` would move to, without moving.
+    pub fn peek_prev(&self) -> Option<&'a T> {
+        let mut prev = self.clone();
+        prev.move_prev();
+        prev.current()
+    }
+}
+
+/// A cursor over the items of a `BinarySearchTree` that can also add and remove items
+/// where it points. Like `Cursor`, it has a ghost position off the ends of the tree.
+pub struct CursorMut<'a, T> {
+    tree: &'a mut BinarySearchTree<T>,
+    /// Rank of the current item, or the number of items at the ghost position.
+    index: usize,
+}
+
+impl<'a, T> CursorMut<'a, T> {
+    pub(crate) fn new(tree: &'a mut BinarySearchTree<T>, index: usize) -> Self {
+        Self { tree, index }
+    }
+
+    /// Rank of the current item, or the number of items at the ghost position.
+    #[cfg(feature = "trace")]
+    pub(crate) fn index(&self) -> usize {
+        self.index
+    }
+
+    fn item_at(&self, rank: usize) -> Option<&T> {
+        // Safety: we are borrowing the tree.
+        unsafe { select_node(self.tree.root, rank).map(|node| &node.as_ref().item) }
+    }
+
+    /// The item the cursor points at, or `None` at the ghost position.
+    pub fn current(&self) -> Option<&T> {
+        self.item_at(self.index)
+    }
+
+    /// Move to the next item in sorted order, or to the ghost position after the largest one.
+    pub fn move_next(&mut self) {
+        let len = self.tree.len();
+        self.index = if self.index == len { 0 } else { self.index + 1 };
+    }
+
+    /// Move to the previous item in sorted order, or to the ghost position before the smallest one.
+    pub fn move_prev(&mut self) {
+        let len = self.tree.len();
+        self.index = if self.index == 0 { len } else { self.index - 1 };
+    }
+
+    /// The item `move_next` would move to, without moving.
+    pub fn peek_next(&self) -> Option<&T> {
+        if self.index == self.tree.len() {
+            self.item_at(0)
+        } else {
+            self.item_at(self.index + 1)
+        }
+    }
+
+    /// The item `move_prev` would move to, without moving.
+    pub fn peek_prev(&self) -> Option<&T> {
+        match self.index {
+            0 => None,
+            index => self.item_at(index - 1),
+        }
+    }
+
+    /// A read-only cursor pointing at the same item.
+    pub fn as_cursor(&self) -> Cursor<'_, T> {
+        // Safety: the tree is borrowed for as long as the cursor.
+        unsafe {
+            Cursor::new(
+                self.tree.root,
+                Position::at_rank(self.tree.root, self.index),
+            )
+        }
+    }
+
+    /// Remove the current item and move to the next one, handing the removed item back.
+    /// Does nothing at the ghost position.
+    pub fn remove_current(&mut self) -> Option<T> {
+        if self.index == self.tree.len() {
+            return None;
+        }
+        // Safety: the current item is in the tree, which we have unique access to.
+        Some(unsafe { remove_rank_node(&mut self.tree.nodes, &mut self.tree.root, self.index) })
+    }
+}
+
+impl<T: Ord> CursorMut<'_, T> {
+    /// Insert an item just before the current one, or as the largest item at the ghost position.
+    /// If that would put the item out of order, or next to an equal one in a tree whose
+    /// duplicate policy doesn't allow that, it is handed back instead.
+    pub fn insert_before(&mut self, value: T) -> Result<(), T> {
+        self.insert_at(self.index, value)?;
+        self.index += 1;
+        Ok(())
+    }
+
+    /// Insert an item just after the current one, or as the smallest item at the ghost position.
+    /// If that would put the item out of order, or next to an equal one in a tree whose
+    /// duplicate policy doesn't allow that, it is handed back instead.
+    pub fn insert_after(&mut self, value: T) -> Result<(), T> {
+        if self.index == self.tree.len() {
+            self.insert_at(0, value)?;
+            self.index += 1;
+        } else {
+            self.insert_at(self.index + 1, value)?;
+        }
+        Ok(())
+    }
+
+    /// Insert an item so that it ends up with the given rank, if it fits there.
+    fn insert_at(&mut self, rank: usize, value: T) -> Result<(), T> {
+        let after_prev = rank == 0
+            || self
+                .item_at(rank - 1)
+                .is_some_and(|prev| self.tree.in_order(prev, &value));
+        let before_next = self
+            .item_at(rank)
+            .is_none_or(|next| self.tree.in_order(&value, next));
+        if !(after_prev && before_next) {
+            return Err(value);
+        }
+        // Safety: the tree has at least `rank` items, and we have unique access to it.
+        let path_len = unsafe {
+            let node = self.tree.nodes.alloc(value);
+            insert_rank_node(&mut self.tree.root, node, rank)
+        };
+        #[cfg(feature = "stats")]
+        self.tree.stats.record_insert(path_len);
+        #[cfg(not(feature = "stats"))]
+        let _ = path_len;
+        Ok(())
+    }
+}
+
+impl<T> BinarySearchTree<T> {
+    /// A cursor pointing at the smallest item, or at the ghost position if the tree is empty.
+    pub fn cursor_front(&self) -> Cursor<'_, T> {
+        // Safety: the tree is borrowed for as long as the cursor.
+        unsafe { Cursor::new(self.root, Position::first(self.root)) }
+    }
+
+    /// A cursor pointing at the largest item, or at the ghost position if the tree is empty.
+    pub fn cursor_back(&self) -> Cursor<'_, T> {
+        // Safety: as above.
+        unsafe { Cursor::new(self.root, Position::last(self.root)) }
+    }
+
+    /// A cursor pointing at the smallest item not less than `key`,
+    /// or at the ghost position if there is none.
+    pub fn cursor_at<Q>(&self, key: &Q) -> Cursor<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // Safety: as above.
+        unsafe {
+            let rank = count_below(self.root, &mut |item: &T| item.borrow() < key);
+            Cursor::new(self.root, Position::at_rank(self.root, rank))
+        }
+    }
+
+    /// A mutable cursor pointing at the smallest item, or at the ghost position if the tree is empty.
+    pub fn cursor_front_mut(&mut self) -> CursorMut<'_, T> {
+        CursorMut::new(self, 0)
+    }
+
+    /// A mutable cursor pointing at the largest item, or at the ghost position if the tree is empty.
+    pub fn cursor_back_mut(&mut self) -> CursorMut<'_, T> {
+        let index = self.len().saturating_sub(1);
+        CursorMut::new(self, index)
+    }
+
+    /// A mutable cursor pointing at the smallest item not less than `key`,
+    /// or at the ghost position if there is none.
+    pub fn cursor_at_mut<Q>(&mut self, key: &Q) -> CursorMut<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // Safety: the tree is borrowed for the whole walk.
+        let index = unsafe { count_below(self.root, &mut |item: &T| item.borrow() < key) };
+        CursorMut::new(self, index)
+    }
+}
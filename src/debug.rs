@@ -0,0 +1,183 @@
+use std::{
+    borrow::Borrow,
+    cmp::Ordering,
+    fmt::{self, Debug, Write},
+    ptr::NonNull,
+};
+
+use crate::{BinarySearchTree, Link, Node, Position};
+
+/// Which way a search went after comparing the key with an item, as reported by `explain`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// The key was less than the item.
+    Left,
+    /// The key was greater than the item.
+    Right,
+    /// The key was equal to the item, which ended the search.
+    Found,
+}
+
+/// One comparison made while searching a tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Comparison<'a, T> {
+    pub item: &'a T,
+    pub direction: Direction,
+}
+
+impl<T: Ord> BinarySearchTree<T> {
+    /// Search for `key` the same way `get` does, returning every item it was compared with,
+    /// and which way the search went after each. If the key isn't there, the last step
+    /// goes off the bottom of the tree.
+    pub fn explain<Q>(&self, key: &Q) -> Vec<Comparison<'_, T>>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut steps = Vec::new();
+        let mut current = self.root;
+        while let Some(node) = current {
+            // Safety: the node belongs to this tree, which we are borrowing.
+            let node = unsafe { node.as_ref() };
+            let (direction, next) = match key.cmp(node.item.borrow()) {
+                Ordering::Less => (Direction::Left, node.left),
+                Ordering::Greater => (Direction::Right, node.right),
+                Ordering::Equal => (Direction::Found, None),
+            };
+            steps.push(Comparison {
+                item: &node.item,
+                direction,
+            });
+            current = next;
+        }
+        steps
+    }
+}
+
+/// Formats a node's item, followed by its ID when nodes are numbered.
+struct Label<'a, T>(&'a Node<T>);
+
+impl<T: Debug> Debug for Label<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.0.item, f)?;
+        #[cfg(feature = "node-ids")]
+        write!(f, " (#{})", self.0.id)?;
+        Ok(())
+    }
+}
+
+impl<T: Debug> Debug for BinarySearchTree<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut set = f.debug_set();
+        // Safety: the tree is valid, and borrowed for the whole walk.
+        let mut next = unsafe { Position::first(self.root) };
+        while let Some(node) = next.node() {
+            // Safety: the node belongs to this tree, which we are borrowing.
+            unsafe {
+                set.entry(&Label(node.as_ref()));
+                next.move_next();
+            }
+        }
+        set.finish()
+    }
+}
+
+impl<T: Debug> BinarySearchTree<T> {
+    /// Render the shape of the tree as a Graphviz DOT graph, with each node labelled
+    /// by its item (and its ID, with the `node-ids` feature).
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph {\n");
+        let mut count = 0;
+        // Safety: the tree is valid, and borrowed for the whole walk.
+        unsafe { write_dot(&mut out, self.root, &mut count) };
+        out.push_str("}\n");
+        out
+    }
+}
+
+impl<T: Ord + Debug> BinarySearchTree<T> {
+    /// Check that every node is in order with its ancestors, and that its size and parent
+    /// link are right.
+    ///
+    /// Panics with a message naming the first broken node if they aren't.
+    pub fn check_invariants(&self) {
+        if let Some(root) = self.root {
+            // Safety: the tree is valid, and borrowed for the whole walk.
+            unsafe { check_node(root, None, None, None) };
+        }
+    }
+}
+
+/// Write the subtree at `l` as DOT statements, naming nodes in pre-order starting from
+/// `count`, and return the name of its root.
+/// Safety: l must be `None` or a valid tree.
+unsafe fn write_dot<T: Debug>(out: &mut String, l: Link<T>, count: &mut usize) -> Option<usize> {
+    // Safety: guaranteed by the caller.
+    let node = unsafe { l?.as_ref() };
+    let name = *count;
+    *count += 1;
+    let label = format!("{:?}", Label(node))
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+    let _ = writeln!(out, "    n{name} [label=\"{label}\"];");
+    for (side, child) in [("left", node.left), ("right", node.right)] {
+        // Safety: the children of a valid node are valid.
+        if let Some(child) = unsafe { write_dot(out, child, count) } {
+            let _ = writeln!(out, "    n{name} -> n{child} [label=\"{side}\"];");
+        }
+    }
+    Some(name)
+}
+
+/// Check the subtree at `node`, whose items must lie between those of `low` and `high`,
+/// and return its size.
+/// Safety: node must be a valid tree, and `parent`, `low` and `high` valid nodes, if any.
+unsafe fn check_node<T: Ord + Debug>(
+    node: NonNull<Node<T>>,
+    parent: Link<T>,
+    low: Link<T>,
+    high: Link<T>,
+) -> usize {
+    // Safety: guaranteed by the caller.
+    let this = unsafe { node.as_ref() };
+    if let Some(low) = low.map(|low| unsafe { low.as_ref() }) {
+        assert!(
+            low.item <= this.item,
+            "node {:?} is less than its ancestor {:?}",
+            Label(this),
+            Label(low)
+        );
+    }
+    if let Some(high) = high.map(|high| unsafe { high.as_ref() }) {
+        assert!(
+            this.item <= high.item,
+            "node {:?} is greater than its ancestor {:?}",
+            Label(this),
+            Label(high)
+        );
+    }
+    #[cfg(feature = "parent-pointers")]
+    assert!(
+        this.parent == parent,
+        "node {:?} has the wrong parent link",
+        Label(this)
+    );
+    #[cfg(not(feature = "parent-pointers"))]
+    let _ = parent;
+
+    // Safety: the children of a valid node are valid, and lie on either side of it.
+    let left = this.left.map_or(0, |left| unsafe {
+        check_node(left, Some(node), low, Some(node))
+    });
+    let right = this.right.map_or(0, |right| unsafe {
+        check_node(right, Some(node), Some(node), high)
+    });
+    assert!(
+        this.size == left + right + 1,
+        "node {:?} has size {}, but its subtree has {} nodes",
+        Label(this),
+        this.size,
+        left + right + 1
+    );
+    this.size
+}
@@ -0,0 +1,66 @@
+use std::{cell::RefCell, ptr::NonNull};
+
+use crate::Node;
+
+/// Which child of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A single step taken by an operation on a tree, naming nodes by their IDs.
+/// The tree is never rebalanced behind the caller's back, so nothing ever rotates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// The key being searched for or inserted was compared with a node's item.
+    Compare { node: u64 },
+    /// The operation moved down from a node to one of its children, or off the bottom of the tree.
+    Descend { from: u64, side: Side },
+    /// A new node was hung off a parent, or became the root if there is none.
+    Attach {
+        node: u64,
+        parent: Option<(u64, Side)>,
+    },
+    /// A removed node was replaced by its only child, or by nothing.
+    Replace { node: u64, with: Option<u64> },
+    /// A removed node with two children was replaced by the smallest node greater than it.
+    ReplaceWithSuccessor { node: u64, successor: u64 },
+}
+
+thread_local! {
+    static EVENTS: RefCell<Option<Vec<Event>>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, collecting every event emitted by trees on this thread while it runs.
+pub fn record_events<R>(f: impl FnOnce() -> R) -> (R, Vec<Event>) {
+    /// Puts back whatever was being recorded before, even if `f` panics.
+    struct Restore(Option<Vec<Event>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            EVENTS.with_borrow_mut(|events| *events = self.0.take());
+        }
+    }
+
+    let outer = EVENTS.with_borrow_mut(|events| events.replace(Vec::new()));
+    let restore = Restore(outer);
+    let result = f();
+    let events = EVENTS.with_borrow_mut(|events| events.take().unwrap_or_default());
+    drop(restore);
+    (result, events)
+}
+
+/// Record an event, if anyone on this thread is listening.
+pub(crate) fn emit(event: Event) {
+    EVENTS.with_borrow_mut(|events| {
+        if let Some(events) = events {
+            events.push(event);
+        }
+    });
+}
+
+/// Safety: node must be valid.
+pub(crate) unsafe fn id<T>(node: NonNull<Node<T>>) -> u64 {
+    unsafe { node.as_ref() }.id
+}
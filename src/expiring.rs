@@ -0,0 +1,78 @@
+use crate::{BinarySearchTree, BstMap};
+
+/// A map whose entries each carry a deadline, after which `purge_expired` throws them away.
+/// Deadlines can be any ordered type, such as an `Instant` or a tick count.
+pub struct ExpiringMap<K, V, D> {
+    /// The value and deadline of every entry, by key.
+    entries: BstMap<K, (V, D)>,
+    /// The deadline and key of every entry, soonest first.
+    deadlines: BinarySearchTree<(D, K)>,
+}
+
+impl<K, V, D> Default for ExpiringMap<K, V, D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, D> ExpiringMap<K, V, D> {
+    pub fn new() -> Self {
+        Self {
+            entries: BstMap::new(),
+            deadlines: BinarySearchTree::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<K: Ord + Clone, V, D: Ord + Clone> ExpiringMap<K, V, D> {
+    /// Insert an entry that expires at `deadline`, returning the value it replaced, if any.
+    pub fn insert(&mut self, key: K, value: V, deadline: D) -> Option<V> {
+        let old = self.remove(&key);
+        self.deadlines.insert((deadline.clone(), key.clone()));
+        self.entries.insert(key, (value, deadline));
+        old
+    }
+
+    /// The value of an entry, whether or not its deadline has passed.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|(value, _)| value)
+    }
+
+    pub fn deadline(&self, key: &K) -> Option<&D> {
+        self.entries.get(key).map(|(_, deadline)| deadline)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let (key, (value, deadline)) = self.entries.remove_entry(key)?;
+        self.deadlines.delete(&(deadline, key));
+        Some(value)
+    }
+
+    /// The soonest deadline of any entry.
+    pub fn next_deadline(&self) -> Option<&D> {
+        self.deadlines.min().map(|(deadline, _)| deadline)
+    }
+
+    /// Remove every entry whose deadline is at or before `now`, returning them soonest first.
+    pub fn purge_expired(&mut self, now: &D) -> Vec<(K, V)> {
+        let mut expired = Vec::new();
+        while let Some((deadline, key)) = self.deadlines.min() {
+            if deadline > now {
+                break;
+            }
+            let key = key.clone();
+            if let Some(value) = self.remove(&key) {
+                expired.push((key, value));
+            }
+        }
+        expired
+    }
+}
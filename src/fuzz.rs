@@ -0,0 +1,34 @@
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use crate::{remove_rank_node, BinarySearchTree};
+
+/// Builds a tree by playing out an arbitrary run of inserts, removals and rebuilds, so that
+/// fuzz targets see lopsided and balanced shapes alike, pooled or not, rather than only
+/// whatever shape inserting the items in order would give.
+impl<'a, T> Arbitrary<'a> for BinarySearchTree<T>
+where
+    T: Arbitrary<'a> + Ord,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut tree = if u.arbitrary()? {
+            Self::new_pooled()
+        } else {
+            Self::new()
+        };
+        // Keep going until the input says to stop, or runs out.
+        while u.arbitrary()? {
+            match u.int_in_range(0..=7)? {
+                0 if !tree.is_empty() => {
+                    let rank = u.choose_index(tree.len())?;
+                    // Safety: the rank is in range, and we own the tree.
+                    unsafe { remove_rank_node(&mut tree.nodes, &mut tree.root, rank) };
+                }
+                1 => tree.compact(),
+                _ => {
+                    tree.insert(u.arbitrary()?);
+                }
+            }
+        }
+        Ok(tree)
+    }
+}
@@ -0,0 +1,122 @@
+use std::{
+    borrow::Borrow,
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+use crate::BinarySearchTree;
+
+/// A 64-bit FNV-1a hasher. Unlike the standard library's hasher, its output is fixed,
+/// so hashes can be compared between processes and builds.
+struct Fnv(u64);
+
+impl Hasher for Fnv {
+    fn finish(&self) -> u64 {
+        // Mix the bits well, so that adding up item hashes doesn't cancel out structure.
+        let mut x = self.0;
+        x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        x ^ (x >> 31)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= u64::from(byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+}
+
+fn item_hash<T: Hash>(item: &T) -> u64 {
+    let mut hasher = Fnv(0xcbf2_9ce4_8422_2325);
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// A tree that keeps a hash of its contents up to date as it changes.
+/// It can be read like the tree it wraps, but changes have to go through its own methods.
+pub struct Hashed<T> {
+    tree: BinarySearchTree<T>,
+    hash: u64,
+}
+
+impl<T> Deref for Hashed<T> {
+    type Target = BinarySearchTree<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T> Default for Hashed<T> {
+    fn default() -> Self {
+        Self {
+            tree: BinarySearchTree::new(),
+            hash: 0,
+        }
+    }
+}
+
+impl<T: Hash> From<BinarySearchTree<T>> for Hashed<T> {
+    /// Start keeping track of a tree's hash, which takes one pass over its items.
+    fn from(tree: BinarySearchTree<T>) -> Self {
+        let hash = tree
+            .iter()
+            .fold(0, |hash: u64, item| hash.wrapping_add(item_hash(item)));
+        Self { tree, hash }
+    }
+}
+
+impl<T> Hashed<T> {
+    /// A hash of the items in the tree, which doesn't depend on its shape or on the order the
+    /// items were inserted in. Trees with the same items have the same hash, so if two trees'
+    /// hashes differ, so do their items. Equal hashes make equal items very likely, but not certain.
+    pub fn content_hash(&self) -> u64 {
+        self.hash
+    }
+
+    pub fn into_inner(self) -> BinarySearchTree<T> {
+        self.tree
+    }
+
+    /// Drop every item, leaving the tree empty and its hash zero.
+    pub fn clear(&mut self) {
+        self.tree.clear();
+        self.hash = 0;
+    }
+}
+
+impl<T: Ord + Hash> Hashed<T> {
+    pub fn insert(&mut self, value: T) -> Option<T> {
+        self.hash = self.hash.wrapping_add(item_hash(&value));
+        let out = self.tree.insert(value);
+        if let Some(out) = &out {
+            self.hash = self.hash.wrapping_sub(item_hash(out));
+        }
+        out
+    }
+
+    pub fn delete(&mut self, item: &T) {
+        self.remove(item);
+    }
+
+    /// Remove an item equal to `key`, handing it back if there was one.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let removed = self.tree.remove(key)?;
+        self.hash = self.hash.wrapping_sub(item_hash(&removed));
+        Some(removed)
+    }
+
+    /// The same as `remove`, under the name `BTreeSet` uses.
+    pub fn take<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove(key)
+    }
+}
@@ -0,0 +1,312 @@
+use std::{fmt, iter::FusedIterator, ops::Range, ptr::NonNull};
+
+use crate::{
+    count_below, insert_rank_node, remove_rank_node, size_of, BinarySearchTree, Iter, Link, Node,
+};
+
+/// An interval and its value, as stored in an `IntervalTree`'s nodes. Ordered by the start of
+/// the interval alone, so the set machinery can insert and walk them as if they were plain items.
+#[derive(Clone, Debug)]
+struct IntervalEntry<T, V> {
+    interval: Range<T>,
+    value: V,
+    /// Largest end of any interval in the subtree rooted at this entry's node.
+    max_end: T,
+}
+
+impl<T: PartialEq, V> PartialEq for IntervalEntry<T, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.interval.start == other.interval.start
+    }
+}
+
+impl<T: Eq, V> Eq for IntervalEntry<T, V> {}
+
+impl<T: Ord, V> PartialOrd for IntervalEntry<T, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord, V> Ord for IntervalEntry<T, V> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.interval.start.cmp(&other.interval.start)
+    }
+}
+
+/// A collection of half-open intervals, each with a value, that can be searched for the
+/// intervals containing a point or overlapping a range. Intervals are kept in a binary
+/// search tree ordered by start, and each node also knows the largest end in its subtree,
+/// so searches skip subtrees that end too early. The same interval can be added more than
+/// once, and empty intervals never contain or overlap anything.
+#[derive(Clone)]
+pub struct IntervalTree<T, V> {
+    tree: BinarySearchTree<IntervalEntry<T, V>>,
+}
+
+impl<T, V> Default for IntervalTree<T, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, V> IntervalTree<T, V> {
+    pub fn new() -> Self {
+        Self {
+            tree: BinarySearchTree::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Iterate over the intervals and their values, in order of start.
+    pub fn iter(&self) -> IntervalIter<'_, T, V> {
+        IntervalIter {
+            iter: self.tree.iter(),
+        }
+    }
+}
+
+impl<T: Ord + Clone, V> IntervalTree<T, V> {
+    /// Add an interval with a value, after any intervals with the same start.
+    pub fn insert(&mut self, interval: Range<T>, value: V) {
+        let start = interval.start.clone();
+        let tree = &mut self.tree;
+        // Safety: we have unique access to the tree. Equal starts go after the ones already
+        // there, so the new node's rank is the number of entries starting no later than it.
+        unsafe {
+            let rank = count_below(tree.root, &mut |entry: &IntervalEntry<T, V>| {
+                entry.interval.start <= start
+            });
+            let max_end = interval.end.clone();
+            let node = tree.nodes.alloc(IntervalEntry {
+                interval,
+                value,
+                max_end,
+            });
+            let path_len = insert_rank_node(&mut tree.root, node, rank);
+            #[cfg(feature = "stats")]
+            tree.stats.record_insert(path_len);
+            #[cfg(not(feature = "stats"))]
+            let _ = path_len;
+            refresh_max_ends(&rank_path(tree.root, rank));
+        }
+    }
+
+    /// Remove an interval equal to `interval`, handing back its value if there was one.
+    /// If it was added more than once, the earliest added is removed.
+    pub fn remove(&mut self, interval: &Range<T>) -> Option<V> {
+        let tree = &mut self.tree;
+        // Safety: we have unique access to the tree, and only remove a rank we found in it.
+        unsafe {
+            let first = count_below(tree.root, &mut |entry: &IntervalEntry<T, V>| {
+                entry.interval.start < interval.start
+            });
+            let offset = Iter::from_ranks(tree.root, first, tree.len())
+                .take_while(|entry| entry.interval.start == interval.start)
+                .position(|entry| entry.interval.end == interval.end)?;
+            let rank = first + offset;
+            let mut ancestors = rank_path(tree.root, rank);
+            ancestors.pop();
+            let entry = remove_rank_node(&mut tree.nodes, &mut tree.root, rank);
+            // If the removed node had two children, the next node took its place and now has
+            // its rank. If the next node came from further down, the path to the node after it
+            // runs through where it was.
+            refresh_max_ends(&rank_path(tree.root, rank + 1));
+            refresh_max_ends(&rank_path(tree.root, rank));
+            // Either way, every node above the removed one has lost an interval.
+            refresh_max_ends(&ancestors);
+            Some(entry.value)
+        }
+    }
+
+    /// The intervals containing `point`, with their values, in order of start.
+    pub fn containing(&self, point: &T) -> Vec<(&Range<T>, &V)> {
+        self.search(point, |start| start <= point)
+    }
+
+    /// The intervals overlapping `range`, with their values, in order of start.
+    pub fn overlapping(&self, range: &Range<T>) -> Vec<(&Range<T>, &V)> {
+        if range.start >= range.end {
+            return Vec::new();
+        }
+        self.search(&range.start, |start| *start < range.end)
+    }
+
+    /// Walk the tree in order, collecting the non-empty intervals that end after `after` and
+    /// whose starts satisfy `starts_in`, which must hold for some prefix of the starts.
+    fn search<F>(&self, after: &T, starts_in: F) -> Vec<(&Range<T>, &V)>
+    where
+        F: Fn(&T) -> bool,
+    {
+        let mut found = Vec::new();
+        let mut stack = Vec::new();
+        let mut current = self.tree.root;
+        loop {
+            // Safety: every node belongs to this tree, which we are borrowing.
+            while let Some(node) = current.map(|node| unsafe { &*node.as_ptr() }) {
+                if node.item.max_end <= *after {
+                    // Nothing in this subtree ends late enough.
+                    break;
+                }
+                stack.push(node);
+                current = node.left;
+            }
+            let Some(node) = stack.pop() else { break };
+            let entry = &node.item;
+            if !starts_in(&entry.interval.start) {
+                // Nor does anything after it, since those start no earlier.
+                break;
+            }
+            if entry.interval.end > *after && entry.interval.start < entry.interval.end {
+                found.push((&entry.interval, &entry.value));
+            }
+            current = node.right;
+        }
+        found
+    }
+}
+
+/// The nodes on the path down to the node with the given rank, or to the last node if there
+/// are not that many, starting at the root.
+/// Safety: root must be `None` or point to a valid tree.
+unsafe fn rank_path<T>(root: Link<T>, mut rank: usize) -> Vec<NonNull<Node<T>>> {
+    let mut path = Vec::new();
+    let mut current = root;
+    // Safety: guaranteed by the caller.
+    unsafe {
+        while let Some(node) = current {
+            path.push(node);
+            let left = (*node.as_ptr()).left;
+            let left_size = size_of(left);
+            current = match rank.cmp(&left_size) {
+                std::cmp::Ordering::Equal => None,
+                std::cmp::Ordering::Less => left,
+                std::cmp::Ordering::Greater => {
+                    rank -= left_size + 1;
+                    (*node.as_ptr()).right
+                }
+            };
+        }
+    }
+    path
+}
+
+/// Recompute the largest end in the subtree of each node on a path from the root, from the
+/// bottom up, assuming the nodes hanging off the path already know theirs.
+/// Safety: the nodes must be valid and belong to a tree we have unique access to.
+unsafe fn refresh_max_ends<T: Ord + Clone, V>(path: &[NonNull<Node<IntervalEntry<T, V>>>]) {
+    for &node in path.iter().rev() {
+        // Safety: guaranteed by the caller.
+        unsafe {
+            let node = &mut *node.as_ptr();
+            node.item.max_end = max_end_below(node).clone();
+        }
+    }
+}
+
+/// The largest end of the interval in a node and those its children know of.
+/// Safety: the node's children must be valid.
+unsafe fn max_end_below<T: Ord, V>(node: &Node<IntervalEntry<T, V>>) -> &T {
+    let mut max_end = &node.item.interval.end;
+    for child in [node.left, node.right].into_iter().flatten() {
+        // Safety: guaranteed by the caller.
+        let child_max = unsafe { &(*child.as_ptr()).item.max_end };
+        if child_max > max_end {
+            max_end = child_max;
+        }
+    }
+    max_end
+}
+
+impl<T: Ord + Clone + fmt::Debug, V: fmt::Debug> IntervalTree<T, V> {
+    /// Check that the tree underneath is sound, as by `BinarySearchTree::check_invariants`,
+    /// and that every node knows the largest end of any interval in its subtree.
+    ///
+    /// Panics with a message naming the first broken node if they aren't.
+    pub fn check_invariants(&self) {
+        self.tree.check_invariants();
+        // Safety: the tree is valid, and borrowed for the whole walk.
+        unsafe { check_max_ends(self.tree.root) };
+    }
+}
+
+/// Check the largest ends known by every node of a subtree, bottom up.
+/// Safety: l must be `None` or point to a valid tree.
+unsafe fn check_max_ends<T: Ord + fmt::Debug, V: fmt::Debug>(l: Link<IntervalEntry<T, V>>) {
+    let Some(node) = l else { return };
+    // Safety: guaranteed by the caller.
+    unsafe {
+        let node = node.as_ref();
+        check_max_ends(node.left);
+        check_max_ends(node.right);
+        let expected = max_end_below(node);
+        assert!(
+            node.item.max_end == *expected,
+            "node {:?} has largest end {:?} in its subtree, but thinks it is {:?}",
+            node.item.interval,
+            expected,
+            node.item.max_end
+        );
+    }
+}
+
+impl<T: fmt::Debug, V: fmt::Debug> fmt::Debug for IntervalTree<T, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T, V> IntoIterator for &'a IntervalTree<T, V> {
+    type Item = (&'a Range<T>, &'a V);
+    type IntoIter = IntervalIter<'a, T, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the intervals of an `IntervalTree` and their values, in order of start.
+pub struct IntervalIter<'a, T, V> {
+    iter: Iter<'a, IntervalEntry<T, V>>,
+}
+
+impl<T, V> Clone for IntervalIter<'_, T, V> {
+    fn clone(&self) -> Self {
+        Self {
+            iter: self.iter.clone(),
+        }
+    }
+}
+
+impl<'a, T, V> Iterator for IntervalIter<'a, T, V> {
+    type Item = (&'a Range<T>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter
+            .next()
+            .map(|entry| (&entry.interval, &entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T, V> DoubleEndedIterator for IntervalIter<'_, T, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter
+            .next_back()
+            .map(|entry| (&entry.interval, &entry.value))
+    }
+}
+
+impl<T, V> ExactSizeIterator for IntervalIter<'_, T, V> {}
+
+impl<T, V> FusedIterator for IntervalIter<'_, T, V> {}
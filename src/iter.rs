@@ -0,0 +1,870 @@
+use std::{
+    collections::VecDeque,
+    fmt::{self, Debug},
+    iter::FusedIterator,
+    marker::PhantomData,
+    ops::Range,
+    ptr::NonNull,
+};
+
+use crate::{
+    alloc::NodeAlloc, pop_first_node, pop_last_node, remove_rank_node, select_node, size_of,
+    BinarySearchTree, Link, Node, Position, Successor,
+};
+
+/// An iterator over the items of a `BinarySearchTree`, in sorted order.
+pub struct Iter<'a, T> {
+    front: Position<T>,
+    back: Position<T>,
+    /// Number of items between `front` and `back`, inclusive.
+    len: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: an `Iter` only reads items through a shared borrow of the tree, like a `&T` would.
+unsafe impl<T: Sync> Send for Iter<'_, T> {}
+unsafe impl<T: Sync> Sync for Iter<'_, T> {}
+
+impl<'a, T> Iter<'a, T> {
+    pub(crate) fn new(root: Link<T>) -> Self {
+        // Safety: root belongs to a tree that is borrowed for 'a.
+        unsafe { Self::between(Position::first(root), Position::last(root), size_of(root)) }
+    }
+
+    /// Iterate over the items with ranks from `start` up to (but not including) `end`.
+    /// Safety: root must belong to a tree that is borrowed for 'a, with at least `end` items.
+    pub(crate) unsafe fn from_ranks(root: Link<T>, start: usize, end: usize) -> Self {
+        if start >= end {
+            return Self::between(Position::root(None), Position::root(None), 0);
+        }
+        // Safety: guaranteed by the caller.
+        unsafe {
+            Self::between(
+                Position::at_rank(root, start),
+                Position::at_rank(root, end - 1),
+                end - start,
+            )
+        }
+    }
+
+    /// Iterate from `front` to `back`, which must be `len` items apart (counting both).
+    pub(crate) fn between(front: Position<T>, back: Position<T>, len: usize) -> Self {
+        Self {
+            front,
+            back,
+            len,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Iter<'_, T> {
+    fn clone(&self) -> Self {
+        Self::between(self.front.clone(), self.back.clone(), self.len)
+    }
+}
+
+impl<T: Debug> Debug for Iter<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.clone()).finish()
+    }
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // Safety: there are items left, so `front` is on one of them. The tree is borrowed
+        // for 'a, so its nodes are alive and not mutated.
+        unsafe {
+            let node = self.front.node().unwrap_unchecked();
+            self.front.move_next();
+            Some(&node.as_ref().item)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for Iter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // Safety: as in `next`.
+        unsafe {
+            let node = self.back.node().unwrap_unchecked();
+            self.back.move_prev();
+            Some(&node.as_ref().item)
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T> FusedIterator for Iter<'_, T> {}
+
+/// An iterator over the items of a `BinarySearchTree` level by level, starting at the root,
+/// and from left to right within each level.
+pub struct LevelOrder<'a, T> {
+    inner: WithLevels<'a, T>,
+}
+
+/// An iterator over the items of a `BinarySearchTree` in level order, along with their level.
+/// The root is on level 0.
+pub struct WithLevels<'a, T> {
+    queue: VecDeque<(NonNull<Node<T>>, usize)>,
+    len: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: as for `Iter`, items are only read through a shared borrow of the tree.
+unsafe impl<T: Sync> Send for WithLevels<'_, T> {}
+unsafe impl<T: Sync> Sync for WithLevels<'_, T> {}
+
+impl<'a, T> LevelOrder<'a, T> {
+    pub(crate) fn new(root: Link<T>) -> Self {
+        Self {
+            inner: WithLevels {
+                queue: root.map(|root| (root, 0)).into_iter().collect(),
+                // Safety: root belongs to a tree that is borrowed for 'a.
+                len: unsafe { size_of(root) },
+                _marker: PhantomData,
+            },
+        }
+    }
+
+    /// Yield each item's level along with it.
+    pub fn with_levels(self) -> WithLevels<'a, T> {
+        self.inner
+    }
+}
+
+impl<'a, T> Iterator for LevelOrder<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|(_, item)| item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> Iterator for WithLevels<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, level) = self.queue.pop_front()?;
+        // Safety: the tree is borrowed for 'a, so its nodes are alive and not mutated.
+        let node = unsafe { node.as_ref() };
+        self.queue.extend(
+            [node.left, node.right]
+                .into_iter()
+                .flatten()
+                .map(|child| (child, level + 1)),
+        );
+        self.len -= 1;
+        Some((level, &node.item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for LevelOrder<'_, T> {}
+
+impl<T> FusedIterator for LevelOrder<'_, T> {}
+
+impl<T> ExactSizeIterator for WithLevels<'_, T> {}
+
+impl<T> FusedIterator for WithLevels<'_, T> {}
+
+/// An iterator over the items of a `BinarySearchTree` level by level, starting at the root,
+/// going from left to right on even levels and from right to left on odd ones.
+pub struct Zigzag<'a, T> {
+    /// Nodes left on the current level, the next one last.
+    current: Vec<NonNull<Node<T>>>,
+    /// Nodes on the level below, in the order they will be yielded from last to first.
+    below: Vec<NonNull<Node<T>>>,
+    left_to_right: bool,
+    len: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: as for `Iter`, items are only read through a shared borrow of the tree.
+unsafe impl<T: Sync> Send for Zigzag<'_, T> {}
+unsafe impl<T: Sync> Sync for Zigzag<'_, T> {}
+
+impl<'a, T> Zigzag<'a, T> {
+    pub(crate) fn new(root: Link<T>) -> Self {
+        Self {
+            current: root.into_iter().collect(),
+            below: Vec::new(),
+            left_to_right: true,
+            // Safety: root belongs to a tree that is borrowed for 'a.
+            len: unsafe { size_of(root) },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Zigzag<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.current.is_empty() {
+            std::mem::swap(&mut self.current, &mut self.below);
+            self.left_to_right = !self.left_to_right;
+        }
+        // Safety: the tree is borrowed for 'a, so its nodes are alive and not mutated.
+        let node = unsafe { self.current.pop()?.as_ref() };
+        // Stacking the children in the order we are going now reverses them for the next level.
+        if self.left_to_right {
+            self.below.extend(node.left);
+            self.below.extend(node.right);
+        } else {
+            self.below.extend(node.right);
+            self.below.extend(node.left);
+        }
+        self.len -= 1;
+        Some(&node.item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for Zigzag<'_, T> {}
+
+impl<T> FusedIterator for Zigzag<'_, T> {}
+
+/// An iterator over the items of a `BinarySearchTree` in sorted order,
+/// along with the depth of each one's node, the root being at depth 0.
+pub struct WithDepth<'a, T> {
+    /// The left spine of the subtree still to visit, with depths, the next node last.
+    stack: Vec<(NonNull<Node<T>>, usize)>,
+    len: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: as for `Iter`, items are only read through a shared borrow of the tree.
+unsafe impl<T: Sync> Send for WithDepth<'_, T> {}
+unsafe impl<T: Sync> Sync for WithDepth<'_, T> {}
+
+impl<'a, T> WithDepth<'a, T> {
+    pub(crate) fn new(root: Link<T>) -> Self {
+        let mut iter = Self {
+            stack: Vec::new(),
+            // Safety: root belongs to a tree that is borrowed for 'a.
+            len: unsafe { size_of(root) },
+            _marker: PhantomData,
+        };
+        iter.push_left_spine(root, 0);
+        iter
+    }
+
+    fn push_left_spine(&mut self, mut link: Link<T>, mut depth: usize) {
+        while let Some(node) = link {
+            self.stack.push((node, depth));
+            // Safety: the tree is borrowed for 'a, so its nodes are alive and not mutated.
+            link = unsafe { node.as_ref() }.left;
+            depth += 1;
+        }
+    }
+}
+
+impl<'a, T> Iterator for WithDepth<'a, T> {
+    type Item = (usize, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (node, depth) = self.stack.pop()?;
+        // Safety: the tree is borrowed for 'a, so its nodes are alive and not mutated.
+        let node = unsafe { node.as_ref() };
+        self.push_left_spine(node.right, depth + 1);
+        self.len -= 1;
+        Some((depth, &node.item))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for WithDepth<'_, T> {}
+
+impl<T> FusedIterator for WithDepth<'_, T> {}
+
+/// An iterator over the items of a `BinarySearchTree` in pre-order:
+/// each item comes before the items of its left subtree, then those of its right subtree.
+pub struct PreOrder<'a, T> {
+    /// Roots of the subtrees still to visit, the next one last.
+    stack: Vec<NonNull<Node<T>>>,
+    len: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: as for `Iter`, items are only read through a shared borrow of the tree.
+unsafe impl<T: Sync> Send for PreOrder<'_, T> {}
+unsafe impl<T: Sync> Sync for PreOrder<'_, T> {}
+
+impl<'a, T> PreOrder<'a, T> {
+    pub(crate) fn new(root: Link<T>) -> Self {
+        Self {
+            stack: root.into_iter().collect(),
+            // Safety: root belongs to a tree that is borrowed for 'a.
+            len: unsafe { size_of(root) },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for PreOrder<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Safety: the tree is borrowed for 'a, so its nodes are alive and not mutated.
+        let node = unsafe { self.stack.pop()?.as_ref() };
+        self.stack.extend(node.right);
+        self.stack.extend(node.left);
+        self.len -= 1;
+        Some(&node.item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for PreOrder<'_, T> {}
+
+impl<T> FusedIterator for PreOrder<'_, T> {}
+
+/// An iterator over the items of a `BinarySearchTree` in post-order:
+/// each item comes after the items of its left subtree, then those of its right subtree.
+pub struct PostOrder<'a, T> {
+    /// Nodes still to yield, the next one last, and whether their subtrees are already stacked.
+    stack: Vec<(NonNull<Node<T>>, bool)>,
+    len: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: as for `Iter`, items are only read through a shared borrow of the tree.
+unsafe impl<T: Sync> Send for PostOrder<'_, T> {}
+unsafe impl<T: Sync> Sync for PostOrder<'_, T> {}
+
+impl<'a, T> PostOrder<'a, T> {
+    pub(crate) fn new(root: Link<T>) -> Self {
+        Self {
+            stack: root.map(|root| (root, false)).into_iter().collect(),
+            // Safety: root belongs to a tree that is borrowed for 'a.
+            len: unsafe { size_of(root) },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for PostOrder<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, expanded) = self.stack.pop()?;
+            // Safety: the tree is borrowed for 'a, so its nodes are alive and not mutated.
+            let node_ref = unsafe { node.as_ref() };
+            if expanded {
+                self.len -= 1;
+                return Some(&node_ref.item);
+            }
+            self.stack.push((node, true));
+            self.stack
+                .extend(node_ref.right.map(|right| (right, false)));
+            self.stack.extend(node_ref.left.map(|left| (left, false)));
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for PostOrder<'_, T> {}
+
+impl<T> FusedIterator for PostOrder<'_, T> {}
+
+/// An iterator over runs of equal items of a `BinarySearchTree`, in sorted order.
+/// Each run is an iterator over its items.
+pub struct GroupByEqual<'a, T> {
+    root: Link<T>,
+    next: Position<T>,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: as for `Iter`, items are only read through a shared borrow of the tree.
+unsafe impl<T: Sync> Send for GroupByEqual<'_, T> {}
+unsafe impl<T: Sync> Sync for GroupByEqual<'_, T> {}
+
+impl<'a, T> GroupByEqual<'a, T> {
+    pub(crate) fn new(root: Link<T>) -> Self {
+        Self {
+            root,
+            // Safety: root belongs to a tree that is borrowed for 'a.
+            next: unsafe { Position::first(root) },
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: Ord> Iterator for GroupByEqual<'a, T> {
+    type Item = Iter<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = self.next.node()?;
+        let start = self.next.clone();
+        let mut len = 0;
+        // Safety: the tree is borrowed for 'a, so its nodes are alive and not mutated.
+        unsafe {
+            while let Some(node) = self.next.node() {
+                if node.as_ref().item != first.as_ref().item {
+                    break;
+                }
+                len += 1;
+                self.next.move_next();
+            }
+            // The run ends just before wherever we stopped.
+            let end = if self.next.node().is_some() {
+                let mut end = self.next.clone();
+                end.move_prev();
+                end
+            } else {
+                Position::last(self.root)
+            };
+            Some(Iter::between(start, end, len))
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Every group is non-empty, but we don't know how many items are left.
+        (usize::from(self.next.node().is_some()), None)
+    }
+}
+
+impl<T: Ord> FusedIterator for GroupByEqual<'_, T> {}
+
+/// A draining iterator over the items of a range of a `BinarySearchTree`, in sorted order.
+pub struct DrainRange<'a, T> {
+    nodes: &'a mut NodeAlloc<T>,
+    /// The detached nodes that haven't been yielded yet.
+    root: Link<T>,
+}
+
+// Safety: the drained nodes are owned by the iterator until it yields or frees them,
+// like the rest of a tree is owned by the tree.
+unsafe impl<T: Send> Send for DrainRange<'_, T> {}
+unsafe impl<T: Sync> Sync for DrainRange<'_, T> {}
+
+impl<'a, T> DrainRange<'a, T> {
+    pub(crate) fn new(nodes: &'a mut NodeAlloc<T>, root: Link<T>) -> Self {
+        Self { nodes, root }
+    }
+}
+
+impl<T> Iterator for DrainRange<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Safety: the nodes are detached and came from `nodes`, so we can free them as we go.
+        unsafe { pop_first_node(&mut self.root).map(|node| self.nodes.free(node)) }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Safety: the remaining nodes form a valid tree.
+        let len = unsafe { size_of(self.root) };
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for DrainRange<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        // Safety: as in `next`.
+        unsafe { pop_last_node(&mut self.root).map(|node| self.nodes.free(node)) }
+    }
+}
+
+impl<T> ExactSizeIterator for DrainRange<'_, T> {}
+
+impl<T> FusedIterator for DrainRange<'_, T> {}
+
+impl<T> Drop for DrainRange<'_, T> {
+    fn drop(&mut self) {
+        // Safety: the remaining nodes are detached, came from `nodes`, and are never used again.
+        unsafe { self.nodes.free_tree(self.root.take()) };
+    }
+}
+
+/// An iterator that removes the items of a `BinarySearchTree` matching a predicate, in sorted order.
+pub struct ExtractIf<'a, T, F> {
+    tree: &'a mut BinarySearchTree<T>,
+    /// The next item to look at, which has rank `rank`.
+    next: Position<T>,
+    rank: usize,
+    pred: F,
+}
+
+// Safety: the position only points into the tree, which is borrowed mutably,
+// so this is as thread-safe as the borrow and the predicate are.
+unsafe impl<T: Send, F: Send> Send for ExtractIf<'_, T, F> {}
+unsafe impl<T: Sync, F: Sync> Sync for ExtractIf<'_, T, F> {}
+
+impl<'a, T, F> ExtractIf<'a, T, F> {
+    pub(crate) fn new(tree: &'a mut BinarySearchTree<T>, pred: F) -> Self {
+        // Safety: the tree is valid, and borrowed for as long as the iterator.
+        let next = unsafe { Position::first(tree.root) };
+        Self {
+            tree,
+            next,
+            rank: 0,
+            pred,
+        }
+    }
+}
+
+impl<T, F> Iterator for ExtractIf<'_, T, F>
+where
+    F: FnMut(&T) -> bool,
+{
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Safety: the tree is borrowed mutably, and the position is found again after any
+        // change to it, so it always points at the node with rank `rank`.
+        unsafe {
+            while let Some(node) = self.next.node() {
+                if (self.pred)(&node.as_ref().item) {
+                    // Removing the item moves everything after it down a rank.
+                    let item =
+                        remove_rank_node(&mut self.tree.nodes, &mut self.tree.root, self.rank);
+                    self.next = Position::at_rank(self.tree.root, self.rank);
+                    return Some(item);
+                }
+                self.next.move_next();
+                self.rank += 1;
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.tree.len() - self.rank))
+    }
+}
+
+impl<T, F> FusedIterator for ExtractIf<'_, T, F> where F: FnMut(&T) -> bool {}
+
+/// An iterator that moves the items out of a `BinarySearchTree`, in sorted order.
+/// Nodes are freed as their items are taken, and any left over are freed when it is dropped.
+pub struct IntoIter<T> {
+    tree: BinarySearchTree<T>,
+}
+
+impl<T> IntoIter<T> {
+    pub(crate) fn new(tree: BinarySearchTree<T>) -> Self {
+        Self { tree }
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tree.pop_first()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.tree.len();
+        (len, Some(len))
+    }
+}
+
+impl<T> DoubleEndedIterator for IntoIter<T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.tree.pop_last()
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
+
+impl<T> FusedIterator for IntoIter<T> {}
+
+/// An iterator over consecutive pairs of items of a `BinarySearchTree`, in sorted order.
+pub struct Pairs<'a, T> {
+    iter: Iter<'a, T>,
+    prev: Option<&'a T>,
+}
+
+impl<'a, T> Pairs<'a, T> {
+    pub(crate) fn new(mut iter: Iter<'a, T>) -> Self {
+        let prev = iter.next();
+        Self { iter, prev }
+    }
+}
+
+impl<'a, T> Iterator for Pairs<'a, T> {
+    type Item = (&'a T, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let prev = self.prev?;
+        let next = self.iter.next()?;
+        self.prev = Some(next);
+        Some((prev, next))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = if self.prev.is_some() {
+            self.iter.len()
+        } else {
+            0
+        };
+        (len, Some(len))
+    }
+}
+
+impl<T> ExactSizeIterator for Pairs<'_, T> {}
+
+impl<T> FusedIterator for Pairs<'_, T> {}
+
+/// An iterator over the ranges of values missing from a `BinarySearchTree`
+/// within some bounds, in sorted order.
+pub struct Gaps<'a, T> {
+    iter: Iter<'a, T>,
+    next: Option<T>,
+    end: T,
+}
+
+impl<'a, T> Gaps<'a, T> {
+    pub(crate) fn new(iter: Iter<'a, T>, bounds: Range<T>) -> Self {
+        Self {
+            iter,
+            next: Some(bounds.start),
+            end: bounds.end,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Gaps<'a, T>
+where
+    T: Successor + Ord + Clone,
+{
+    type Item = Range<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.next.take()?;
+            if start >= self.end {
+                return None;
+            }
+
+            match self.iter.next() {
+                Some(item) if *item < start => {
+                    self.next = Some(start);
+                }
+                Some(item) if *item == start => {
+                    // No gap here, the next candidate is right after this item.
+                    self.next = start.successor();
+                }
+                Some(item) => {
+                    self.next = item.successor();
+                    let end = item.clone().min(self.end.clone());
+                    return Some(start..end);
+                }
+                None => return Some(start..self.end.clone()),
+            }
+        }
+    }
+}
+
+impl<T> FusedIterator for Gaps<'_, T> where T: Successor + Ord + Clone {}
+
+/// An iterator over the largest items of a `BinarySearchTree`, largest first.
+pub struct TopK<'a, T> {
+    next: Position<T>,
+    remaining: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: as for `Iter`, items are only read through a shared borrow of the tree.
+unsafe impl<T: Sync> Send for TopK<'_, T> {}
+unsafe impl<T: Sync> Sync for TopK<'_, T> {}
+
+impl<'a, T> TopK<'a, T> {
+    pub(crate) fn new(root: Link<T>, k: usize) -> Self {
+        // Don't bother walking down to the maximum if we will never yield it.
+        let root = root.filter(|_| k > 0);
+        // Safety: root belongs to a tree that is borrowed for 'a.
+        unsafe {
+            Self {
+                next: Position::last(root),
+                remaining: k.min(size_of(root)),
+                _marker: PhantomData,
+            }
+        }
+    }
+}
+
+impl<'a, T> Iterator for TopK<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let node = self.next.node()?;
+        self.remaining -= 1;
+        // Safety: the tree is borrowed for 'a, so its nodes are alive and not mutated.
+        unsafe {
+            // Don't walk to the predecessor if we will never yield it.
+            if self.remaining > 0 {
+                self.next.move_prev();
+            }
+            Some(&node.as_ref().item)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for TopK<'_, T> {}
+
+impl<T> FusedIterator for TopK<'_, T> {}
+
+/// An iterator over every `step`th item of a `BinarySearchTree`, in sorted order.
+pub struct Strided<'a, T> {
+    root: Link<T>,
+    rank: usize,
+    step: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: as for `Iter`, items are only read through a shared borrow of the tree.
+unsafe impl<T: Sync> Send for Strided<'_, T> {}
+unsafe impl<T: Sync> Sync for Strided<'_, T> {}
+
+impl<'a, T> Strided<'a, T> {
+    pub(crate) fn new(root: Link<T>, step: usize) -> Self {
+        assert!(step != 0, "step must be non-zero");
+        Self {
+            root,
+            rank: 0,
+            step,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Iterator for Strided<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Safety: the tree is borrowed for 'a, so its nodes are alive and not mutated.
+        unsafe {
+            let node = select_node(self.root, self.rank)?;
+            self.rank = self.rank.saturating_add(self.step);
+            Some(&node.as_ref().item)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // Safety: as above.
+        let len = unsafe { size_of(self.root) };
+        let remaining = len.saturating_sub(self.rank).div_ceil(self.step);
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T> ExactSizeIterator for Strided<'_, T> {}
+
+impl<T> FusedIterator for Strided<'_, T> {}
+
+/// An iterator over the items of a `BinarySearchTree` in a random order.
+#[cfg(feature = "rand")]
+pub struct Shuffled<'a, 'r, T, R: ?Sized> {
+    root: Link<T>,
+    rng: &'r mut R,
+    remaining: usize,
+    /// Sparse Fisher-Yates state: ranks that have been swapped out of their slot.
+    swapped: std::collections::HashMap<usize, usize>,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: items are only read through a shared borrow of the tree, as for `Iter`,
+// and the random number generator is borrowed mutably like any other field.
+#[cfg(feature = "rand")]
+unsafe impl<T: Sync, R: Send + ?Sized> Send for Shuffled<'_, '_, T, R> {}
+#[cfg(feature = "rand")]
+unsafe impl<T: Sync, R: Sync + ?Sized> Sync for Shuffled<'_, '_, T, R> {}
+
+#[cfg(feature = "rand")]
+impl<'a, 'r, T, R: ?Sized> Shuffled<'a, 'r, T, R> {
+    pub(crate) fn new(root: Link<T>, rng: &'r mut R) -> Self {
+        Self {
+            root,
+            rng,
+            // Safety: root belongs to a tree that is borrowed for 'a.
+            remaining: unsafe { size_of(root) },
+            swapped: std::collections::HashMap::new(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "rand")]
+impl<'a, 'r, T, R> Iterator for Shuffled<'a, 'r, T, R>
+where
+    R: rand::Rng + ?Sized,
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        // Pick one of the remaining slots, then move the last slot into its place.
+        let slot = self.rng.random_range(0..self.remaining);
+        self.remaining -= 1;
+        let last = self
+            .swapped
+            .remove(&self.remaining)
+            .unwrap_or(self.remaining);
+        let rank = if slot == self.remaining {
+            last
+        } else {
+            self.swapped.insert(slot, last).unwrap_or(slot)
+        };
+
+        // Safety: rank is below the tree's size, and the tree is borrowed for 'a.
+        unsafe { select_node(self.root, rank).map(|node| &node.as_ref().item) }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+#[cfg(feature = "rand")]
+impl<T, R> ExactSizeIterator for Shuffled<'_, '_, T, R> where R: rand::Rng + ?Sized {}
+
+#[cfg(feature = "rand")]
+impl<T, R> FusedIterator for Shuffled<'_, '_, T, R> where R: rand::Rng + ?Sized {}
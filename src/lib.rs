@@ -1,37 +1,176 @@
 #![feature(dropck_eyepatch)]
 #![warn(unsafe_op_in_unsafe_fn)]
 
-use std::{borrow::Borrow, marker::PhantomData, ptr::NonNull};
+use alloc::NodeAlloc;
+use std::{
+    borrow::Borrow,
+    collections::{BTreeSet, BinaryHeap},
+    convert::Infallible,
+    error::Error,
+    fmt,
+    hash::{Hash, Hasher},
+    iter::{Cloned, Copied},
+    marker::PhantomData,
+    mem::ManuallyDrop,
+    ops::{Bound, Index, Range, RangeBounds, Sub, SubAssign},
+    ptr::NonNull,
+};
+
+mod alloc;
+mod bounded;
+mod build;
+mod cursor;
+mod debug;
+#[cfg(feature = "events")]
+mod events;
+mod expiring;
+#[cfg(feature = "arbitrary")]
+mod fuzz;
+mod hashed;
+mod interval;
+mod iter;
+mod map;
+mod multiset;
+#[cfg(feature = "rayon")]
+mod par;
+mod prefix;
+#[cfg(feature = "stats")]
+mod stats;
+#[cfg(feature = "proptest")]
+pub mod strategies;
+mod successor;
+#[cfg(feature = "trace")]
+mod trace;
+mod watch;
+
+pub use bounded::{BoundedTree, EvictionPolicy};
+pub use build::{SortError, SortErrorKind};
+pub use cursor::{Cursor, CursorMut};
+pub use debug::{Comparison, Direction};
+pub use expiring::ExpiringMap;
+pub use hashed::Hashed;
+pub use interval::{IntervalIter, IntervalTree};
+pub use iter::{
+    DrainRange, ExtractIf, Gaps, GroupByEqual, IntoIter, Iter, LevelOrder, Pairs, PostOrder,
+    PreOrder, Strided, TopK, WithDepth, WithLevels, Zigzag,
+};
+pub use map::{
+    BstMap, Entry, Keys, MapIter, MapIterMut, OccupiedEntry, VacantEntry, Values, ValuesMut,
+};
+pub use multiset::{BstMultiSet, MultiIter};
+pub use prefix::KeyPrefix;
+pub use successor::Successor;
+pub use watch::{Change, WatchId, Watched};
+
+#[cfg(feature = "events")]
+pub use events::{record_events, Event, Side};
+#[cfg(feature = "rand")]
+pub use iter::Shuffled;
+#[cfg(feature = "rayon")]
+pub use par::{ParIter, ParIterMut, ParValuesMut};
+
+#[cfg(feature = "stats")]
+pub use stats::Stats;
+
+#[cfg(feature = "trace")]
+pub use trace::{Op, Trace, Traced, TracedCursorMut};
+
+/// A possibly empty pointer to a node, i.e. the root of a possibly empty subtree.
+type Link<T> = Option<NonNull<Node<T>>>;
 
 struct Node<T> {
     item: T,
-    parent: Option<NonNull<Node<T>>>,
-    left: Option<NonNull<Node<T>>>,
-    right: Option<NonNull<Node<T>>>,
+    /// Sequence number of the node within the tree it was first inserted into.
+    #[cfg(feature = "node-ids")]
+    id: u64,
+    #[cfg(feature = "parent-pointers")]
+    parent: Link<T>,
+    left: Link<T>,
+    right: Link<T>,
+    /// Number of nodes in the subtree rooted here, including this one.
+    size: usize,
+}
+
+/// What to do with an item equal to one already in a tree, or equal to the one before it in
+/// sorted input. Every way of adding items to a tree follows its policy, whether one at a time
+/// or in bulk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DuplicatePolicy {
+    /// Keep both, the later one after the earlier.
+    Allow,
+    /// Keep the earlier one. Sorted input containing duplicates is an error.
+    Reject,
+    /// Keep the later one.
+    Replace,
 }
 
 pub struct BinarySearchTree<T> {
-    root: Option<NonNull<Node<T>>>,
+    root: Link<T>,
+    nodes: NodeAlloc<T>,
+    /// What to do with items equal to ones already in the tree.
+    duplicates: DuplicatePolicy,
+    #[cfg(feature = "stats")]
+    stats: stats::Counters,
     _marker: PhantomData<Node<T>>,
 }
 
+/// A run of consecutive items of a tree, as produced by `BinarySearchTree::histogram`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bucket<'a, T> {
+    pub first: &'a T,
+    pub last: &'a T,
+    pub count: usize,
+}
+
+/// The error returned by `BinarySearchTree::try_insert` when an equal item is already in the tree.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OccupiedError<'a, T> {
+    /// The item already in the tree.
+    pub existing: &'a T,
+    /// The item that wasn't inserted.
+    pub value: T,
+}
+
+impl<T: fmt::Debug> fmt::Display for OccupiedError<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "failed to insert {:?}, as {:?} is already in the tree",
+            self.value, self.existing
+        )
+    }
+}
+
+impl<T: fmt::Debug> Error for OccupiedError<'_, T> {}
+
 unsafe impl<#[may_dangle] T> Drop for BinarySearchTree<T> {
     fn drop(&mut self) {
-        if let Some(root) = self.root {
-            unsafe {
-                dispose_node(root);
-            }
+        unsafe {
+            self.nodes.dispose(self.root);
         }
     }
 }
 
+// Safety: a tree owns its nodes outright, the same as if they were boxed,
+// so it can be moved to another thread along with its items.
+unsafe impl<T: Send> Send for BinarySearchTree<T> {}
+
+// Safety: through a shared reference the nodes are only ever read, and the stats
+// counters, which are updated through shared references, are atomic.
+unsafe impl<T: Sync> Sync for BinarySearchTree<T> {}
+
 impl<'a, T> Node<T> {
     pub fn new(item: T) -> Self {
         Self {
             item,
+            #[cfg(feature = "node-ids")]
+            id: 0,
+            #[cfg(feature = "parent-pointers")]
             parent: None,
             left: None,
             right: None,
+            size: 1,
         }
     }
 
@@ -73,269 +212,2429 @@ unsafe fn dispose_node<T>(mut l: NonNull<Node<T>>) {
     let _ = unsafe { Box::from_raw(l.as_ptr()) };
 }
 
-/// Insert a node into a tree.
-/// Safety: if l or parent are `Some`, then they must
-/// point to correctly aligned and allocated memory for `Node<T>`.
-unsafe fn insert_node<T>(
-    l: &mut Option<NonNull<Node<T>>>,
-    item: T,
-    parent: Option<NonNull<Node<T>>>,
-) where
+/// Hang a detached, childless node off the right spot at the bottom of a tree,
+/// and return the number of nodes passed on the way down.
+/// Safety: root must be `None` or point to a valid tree, and node must be valid and detached.
+unsafe fn insert_node<T>(root: &mut Link<T>, node: NonNull<Node<T>>) -> usize
+where
     T: Ord,
 {
-    if let Some(mut leaf) = *l {
-        let leaf = unsafe { leaf.as_mut() };
-        if item < leaf.item {
-            let left = &mut leaf.left;
-            unsafe { insert_node(left, item, *l) };
-        } else {
-            let right = &mut leaf.right;
-            unsafe { insert_node(right, item, *l) };
+    let mut slot: *mut Link<T> = root;
+    let mut parent = None;
+    let mut path_len = 0;
+    // Safety: every link on the way down is valid, and we have unique access to the tree via `root`.
+    unsafe {
+        while let Some(leaf) = *slot {
+            #[cfg(feature = "events")]
+            events::emit(Event::Compare {
+                node: events::id(leaf),
+            });
+            let leaf = leaf.as_ptr();
+            (*leaf).size += 1;
+            path_len += 1;
+            slot = if node.as_ref().item < (*leaf).item {
+                &mut (*leaf).left
+            } else {
+                &mut (*leaf).right
+            };
+            parent = NonNull::new(leaf);
+            #[cfg(feature = "events")]
+            events::emit(Event::Descend {
+                from: (*leaf).id,
+                side: side_of(leaf, slot),
+            });
         }
-    } else {
-        // Base case, create new node.
-        let mut new_tree = Box::new(Node::new(item));
-        new_tree.parent = parent;
-        let new_tree = Box::into_raw(new_tree);
-        // Safety: new_tree is definitely non-null as we just made it from a *mut Box<T>.
-        let new_tree = unsafe { NonNull::new_unchecked(new_tree) };
+        set_parent(Some(node), parent);
+        *slot = Some(node);
+        #[cfg(feature = "events")]
+        events::emit(Event::Attach {
+            node: events::id(node),
+            parent: parent.map(|parent| (events::id(parent), side_of(parent.as_ptr(), slot))),
+        });
+    }
+    path_len
+}
 
-        *l = Some(new_tree);
+/// Hang a detached, childless node off the bottom of a tree so that it ends up with the given
+/// rank, without comparing any items, and return the number of nodes passed on the way down.
+/// Safety: root must be `None` or point to a valid tree with at least `rank` nodes,
+/// and node must be valid and detached.
+unsafe fn insert_rank_node<T>(
+    root: &mut Link<T>,
+    node: NonNull<Node<T>>,
+    mut rank: usize,
+) -> usize {
+    let mut slot: *mut Link<T> = root;
+    let mut parent = None;
+    let mut path_len = 0;
+    // Safety: every link on the way down is valid, and we have unique access to the tree via `root`.
+    unsafe {
+        while let Some(leaf) = *slot {
+            let leaf = leaf.as_ptr();
+            (*leaf).size += 1;
+            path_len += 1;
+            let left_size = size_of((*leaf).left);
+            slot = if rank <= left_size {
+                &mut (*leaf).left
+            } else {
+                rank -= left_size + 1;
+                &mut (*leaf).right
+            };
+            parent = NonNull::new(leaf);
+            #[cfg(feature = "events")]
+            events::emit(Event::Descend {
+                from: (*leaf).id,
+                side: side_of(leaf, slot),
+            });
+        }
+        set_parent(Some(node), parent);
+        *slot = Some(node);
+        #[cfg(feature = "events")]
+        events::emit(Event::Attach {
+            node: events::id(node),
+            parent: parent.map(|parent| (events::id(parent), side_of(parent.as_ptr(), slot))),
+        });
     }
+    path_len
 }
 
-unsafe fn search_node<T, Q>(
-    l: Option<NonNull<Node<T>>>,
-    item: &'_ Q,
-    called_once: bool,
-) -> (bool, Option<NonNull<Node<T>>>)
+unsafe fn search_node<T, Q>(l: Link<T>, item: &'_ Q) -> Link<T>
 where
     T: Borrow<Q> + Ord,
     Q: Ord + ?Sized,
 {
-    if let Some(leaf) = l {
-        let leaf_ref = unsafe { leaf.as_ref() };
-        match item.cmp(leaf_ref.item.borrow()) {
-            std::cmp::Ordering::Equal => (called_once, Some(leaf)),
-            std::cmp::Ordering::Less => unsafe { search_node(leaf_ref.left, item, false) },
-            std::cmp::Ordering::Greater => unsafe { search_node(leaf_ref.right, item, false) },
-        }
+    unsafe { search_path(l, item) }.0
+}
+
+/// Which side of `parent` a slot belongs to.
+/// Safety: parent must be valid, and slot must be one of its child links.
+#[cfg(feature = "events")]
+unsafe fn side_of<T>(parent: *mut Node<T>, slot: *mut Link<T>) -> Side {
+    if slot == unsafe { &raw mut (*parent).left } {
+        Side::Left
     } else {
-        (called_once, None)
+        Side::Right
     }
 }
 
-unsafe fn delete_node<T>(node: Option<&mut NonNull<Node<T>>>) -> bool
+/// Find a node equal to `item`, along with the number of nodes visited looking for it.
+/// Safety: l must be `None` or point to a valid tree.
+unsafe fn search_path<T, Q>(l: Link<T>, item: &'_ Q) -> (Link<T>, usize)
 where
-    T: Ord,
+    T: Borrow<Q> + Ord,
+    Q: Ord + ?Sized,
 {
-    if let Some(node) = node {
-        // Safety: We have a &mut reference to this pointer, so nobody else is using it.
-        let node_ref = unsafe { node.as_mut() };
+    unsafe { search_path_by(l, |leaf: &T| item.cmp(leaf.borrow())) }
+}
 
-        match (node_ref.left, node_ref.right) {
-            (None, None) => {
-                // Node has no children, so we just deallocate it.
-                // Safety: - never use node_ref again.
-                //         - node_ref was created from Box<T>, so fulfills contract.
-                unsafe {
-                    let _ = Box::from_raw(node_ref);
-                }
-                true
+/// Find a node for which `cmp` gives `Equal`, along with the number of nodes visited looking
+/// for it. `cmp` says how the item being looked for compares to the item in a node.
+/// Safety: l must be `None` or point to a valid tree.
+unsafe fn search_path_by<T>(
+    l: Link<T>,
+    cmp: impl FnMut(&T) -> std::cmp::Ordering,
+) -> (Link<T>, usize) {
+    let (found, _, path_len) = unsafe { search_rank_by(l, cmp) };
+    (found, path_len)
+}
+
+/// As `search_path_by`, but also give the rank of the node found, or if there is none,
+/// the rank a new item would have to be inserted at.
+/// Safety: l must be `None` or point to a valid tree.
+unsafe fn search_rank_by<T>(
+    l: Link<T>,
+    mut cmp: impl FnMut(&T) -> std::cmp::Ordering,
+) -> (Link<T>, usize, usize) {
+    let mut current = l;
+    let mut rank = 0;
+    let mut path_len = 0;
+    while let Some(leaf) = current {
+        let leaf_ref = unsafe { leaf.as_ref() };
+        path_len += 1;
+        #[cfg(feature = "events")]
+        events::emit(Event::Compare { node: leaf_ref.id });
+        current = match cmp(&leaf_ref.item) {
+            std::cmp::Ordering::Equal => {
+                rank += unsafe { size_of(leaf_ref.left) };
+                return (Some(leaf), rank, path_len);
             }
-            (None, Some(mut right)) => {
-                // Node has one child (right), copy child to node.
-                // Take ownership of right.
-                // Safety: - `right` is never dereferenced again, overwritten with child.right
-                //         - `right` was created from Box<T>, fulfills contract.
-                let child = unsafe { Box::from_raw(right.as_mut()) };
-
-                node_ref.right = child.right;
-                node_ref.left = child.left;
-                node_ref.item = child.item;
-
-                false
-                // child is dropped here
+            std::cmp::Ordering::Less => {
+                #[cfg(feature = "events")]
+                events::emit(Event::Descend {
+                    from: leaf_ref.id,
+                    side: Side::Left,
+                });
+                leaf_ref.left
             }
-            (Some(mut left), None) => {
-                // Node has one child (left), copy child to node.
-                // Take ownership of left.
-                // Safety: - `left` is never dereferenced again, overwritten with child.left
-                //         - `left` was created from Box<T>, fulfills contract.
-                let child = unsafe { Box::from_raw(left.as_mut()) };
-
-                node_ref.right = child.right;
-                node_ref.left = child.left;
-                node_ref.item = child.item;
-
-                false
-                // child is dropped here
+            std::cmp::Ordering::Greater => {
+                #[cfg(feature = "events")]
+                events::emit(Event::Descend {
+                    from: leaf_ref.id,
+                    side: Side::Right,
+                });
+                rank += unsafe { size_of(leaf_ref.left) } + 1;
+                leaf_ref.right
+            }
+        };
+    }
+    (None, rank, path_len)
+}
+
+/// Size of the subtree behind a link.
+/// Safety: if l is `Some`, it must point to a valid node.
+unsafe fn size_of<T>(l: Link<T>) -> usize {
+    l.map_or(0, |node| unsafe { (*node.as_ptr()).size })
+}
+
+/// Find the node holding the item at a given rank (0 being the smallest).
+/// Safety: l must be `None` or point to a valid node.
+unsafe fn select_node<T>(l: Link<T>, mut rank: usize) -> Link<T> {
+    let mut current = l;
+    while let Some(node) = current {
+        let node_ref = unsafe { node.as_ref() };
+        let left_size = unsafe { size_of(node_ref.left) };
+        current = match rank.cmp(&left_size) {
+            std::cmp::Ordering::Equal => return Some(node),
+            std::cmp::Ordering::Less => node_ref.left,
+            std::cmp::Ordering::Greater => {
+                rank -= left_size + 1;
+                node_ref.right
+            }
+        };
+    }
+    None
+}
+
+/// Point a child back at its parent. Without parent pointers there is nothing to do.
+/// Safety: if child is `Some`, it must point to a valid node.
+#[inline]
+unsafe fn set_parent<T>(child: Link<T>, parent: Link<T>) {
+    #[cfg(feature = "parent-pointers")]
+    if let Some(mut child) = child {
+        unsafe { child.as_mut() }.parent = parent;
+    }
+    #[cfg(not(feature = "parent-pointers"))]
+    let _ = (child, parent);
+}
+
+/// Remove the node holding an item equal to `key`, deallocate it, and return its item.
+/// Safety: root must be `None` or point to a valid tree.
+unsafe fn remove_node<T, Q>(nodes: &mut NodeAlloc<T>, root: &mut Link<T>, key: &Q) -> Option<T>
+where
+    T: Borrow<Q> + Ord,
+    Q: Ord + ?Sized,
+{
+    // Find the node first, so that subtree sizes are only touched if it is there.
+    unsafe { search_node(*root, key) }?;
+    Some(unsafe { remove_found_node(nodes, root, key) })
+}
+
+/// Remove the node holding an item equal to `key`, which must be in the tree,
+/// deallocate it, and return its item.
+/// Safety: root must point to a valid tree which holds an item equal to `key`.
+unsafe fn remove_found_node<T, Q>(nodes: &mut NodeAlloc<T>, root: &mut Link<T>, key: &Q) -> T
+where
+    T: Borrow<Q> + Ord,
+    Q: Ord + ?Sized,
+{
+    unsafe { remove_found_node_by(nodes, root, |item: &T| key.cmp(item.borrow())) }
+}
+
+/// Remove the node for which `cmp` gives `Equal`, which must be in the tree,
+/// deallocate it, and return its item. `cmp` is as for `search_path_by`.
+/// Safety: root must point to a valid tree which holds such a node.
+unsafe fn remove_found_node_by<T>(
+    nodes: &mut NodeAlloc<T>,
+    root: &mut Link<T>,
+    mut cmp: impl FnMut(&T) -> std::cmp::Ordering,
+) -> T {
+    let mut slot: *mut Link<T> = root;
+    let mut parent = None;
+    // Safety: the node is on the path we are walking, so every link on the way is valid,
+    // and we have unique access to the tree via `root`.
+    unsafe {
+        loop {
+            let node = (*slot).unwrap_unchecked().as_ptr();
+            slot = match cmp(&(*node).item) {
+                std::cmp::Ordering::Equal => return unlink_node(nodes, &mut *slot, parent),
+                std::cmp::Ordering::Less => &mut (*node).left,
+                std::cmp::Ordering::Greater => &mut (*node).right,
+            };
+            (*node).size -= 1;
+            parent = NonNull::new(node);
+        }
+    }
+}
+
+/// Remove the node holding the item at a given rank, which must be in the tree,
+/// deallocate it, and return its item.
+/// Safety: root must point to a valid tree with more than `rank` items.
+unsafe fn remove_rank_node<T>(nodes: &mut NodeAlloc<T>, root: &mut Link<T>, mut rank: usize) -> T {
+    let mut slot: *mut Link<T> = root;
+    let mut parent = None;
+    // Safety: the node is on the path we are walking, so every link on the way is valid,
+    // and we have unique access to the tree via `root`.
+    unsafe {
+        loop {
+            let node = (*slot).unwrap_unchecked().as_ptr();
+            let left_size = size_of((*node).left);
+            slot = match rank.cmp(&left_size) {
+                std::cmp::Ordering::Equal => return unlink_node(nodes, &mut *slot, parent),
+                std::cmp::Ordering::Less => &mut (*node).left,
+                std::cmp::Ordering::Greater => {
+                    rank -= left_size + 1;
+                    &mut (*node).right
+                }
+            };
+            (*node).size -= 1;
+            parent = NonNull::new(node);
+        }
+    }
+}
+
+/// Unlink the node behind `slot` from the tree, deallocate it, and return its item.
+/// Its ancestors' subtree sizes must be fixed up by the caller.
+/// Safety: slot must hold a valid node whose parent is `parent`, and the node must not be used afterwards.
+unsafe fn unlink_node<T>(nodes: &mut NodeAlloc<T>, slot: &mut Link<T>, parent: Link<T>) -> T {
+    // Safety: the node is owned by the tree, and we have unique access to the tree via `slot`.
+    unsafe {
+        let node = slot.unwrap_unchecked().as_ptr();
+        match ((*node).left, (*node).right) {
+            (None, child) | (child, None) => {
+                // Node has at most one child, which takes its place.
+                *slot = child;
+                set_parent(child, parent);
+                #[cfg(feature = "events")]
+                events::emit(Event::Replace {
+                    node: (*node).id,
+                    with: child.map(|child| events::id(child)),
+                });
             }
             (Some(_), Some(right)) => {
                 // Node has two children.
-                // Solution is to replace this node's value with the left-most descendant of the right child.
+                // Replace it with the left-most descendant of the right child,
                 // i.e., the smallest node that is larger than this one.
-                // Then delete that node.
-                let mut next_biggest = right;
-                while let Some(left) = unsafe { next_biggest.as_mut() }.left {
-                    next_biggest = left;
+                let mut next_biggest = right.as_ptr();
+                let mut next_biggest_parent = node;
+                let mut next_biggest_slot: *mut Link<T> = &mut (*node).right;
+                while let Some(left) = (*next_biggest).left {
+                    (*next_biggest).size -= 1;
+                    next_biggest_parent = next_biggest;
+                    next_biggest_slot = &mut (*next_biggest).left;
+                    next_biggest = left.as_ptr();
                 }
 
-                // Turn next_biggest back into a box
-                // Safety: - next_biggest pointer never used again (shadowed by box)
-                //         - Memory behind pointer valid as was created from Box<T>.
-                let next_biggest = unsafe { Box::from_raw(next_biggest.as_mut()) };
-                if let Some(mut parent) = next_biggest.parent {
-                    unsafe { parent.as_mut() }.left = None;
-                }
-                node_ref.left = next_biggest.left;
-                node_ref.right = next_biggest.right;
-                node_ref.item = (next_biggest).item;
+                // Detach next_biggest from its spot, handing its right child to its parent.
+                *next_biggest_slot = (*next_biggest).right;
+                set_parent((*next_biggest).right, NonNull::new(next_biggest_parent));
 
-                false
+                // Then put it where the node was.
+                (*next_biggest).left = (*node).left;
+                (*next_biggest).right = (*node).right;
+                (*next_biggest).size = (*node).size - 1;
+                let next_biggest = NonNull::new(next_biggest);
+                set_parent((*node).left, next_biggest);
+                set_parent((*node).right, next_biggest);
+                set_parent(next_biggest, parent);
+                *slot = next_biggest;
+                #[cfg(feature = "events")]
+                events::emit(Event::ReplaceWithSuccessor {
+                    node: (*node).id,
+                    successor: events::id(next_biggest.unwrap_unchecked()),
+                });
             }
         }
-    } else {
-        false
+
+        // Safety: the node is no longer reachable from the tree.
+        nodes.free(NonNull::new_unchecked(node))
     }
 }
 
-unsafe fn find_minimum<'a, T>(t: Option<NonNull<Node<T>>>) -> Option<&'a T>
+/// Split a detached tree in two: the nodes whose items satisfy `goes_left`, and the rest.
+/// `goes_left` must hold for some prefix of the items in sorted order, and for none of the others.
+/// Safety: l must be `None` or a valid, detached tree.
+unsafe fn split_node<T, F>(l: Link<T>, goes_left: &mut F) -> (Link<T>, Link<T>)
 where
-    T: Ord,
+    F: FnMut(&T) -> bool,
 {
-    if let Some(t) = t {
-        let mut min = t;
+    let Some(mut node) = l else {
+        return (None, None);
+    };
 
-        loop {
-            match unsafe { min.as_ref() }.left {
-                None => break,
-                Some(left) => {
-                    min = left;
-                }
-            }
+    // Safety: we own the detached tree, so we can relink its nodes however we like.
+    unsafe {
+        let node_ref = node.as_mut();
+        let split = if goes_left(&node_ref.item) {
+            let (left, right) = split_node(node_ref.right, goes_left);
+            node_ref.right = left;
+            set_parent(left, Some(node));
+            (Some(node), right)
+        } else {
+            let (left, right) = split_node(node_ref.left, goes_left);
+            node_ref.left = right;
+            set_parent(right, Some(node));
+            (left, Some(node))
+        };
+        node_ref.size = 1 + size_of(node_ref.left) + size_of(node_ref.right);
+        set_parent(split.0, None);
+        set_parent(split.1, None);
+        split
+    }
+}
+
+/// Detach the largest node of a tree, handing back ownership of it.
+/// Safety: root must be `None` or a valid tree.
+unsafe fn pop_last_node<T>(root: &mut Link<T>) -> Link<T> {
+    let mut slot: *mut Link<T> = root;
+    let mut parent = None;
+    // Safety: every link on the way down is valid, and we have unique access to the tree via `root`.
+    unsafe {
+        let mut node = (*slot)?;
+        while let Some(right) = node.as_ref().right {
+            node.as_mut().size -= 1;
+            parent = Some(node);
+            slot = &mut node.as_mut().right;
+            node = right;
+        }
+        *slot = node.as_ref().left;
+        set_parent(node.as_ref().left, parent);
+        let node_ref = node.as_mut();
+        node_ref.left = None;
+        node_ref.size = 1;
+        set_parent(Some(node), None);
+        Some(node)
+    }
+}
+
+/// Detach the smallest node of a tree, handing back ownership of it.
+/// Safety: root must be `None` or a valid tree.
+unsafe fn pop_first_node<T>(root: &mut Link<T>) -> Link<T> {
+    let mut slot: *mut Link<T> = root;
+    let mut parent = None;
+    // Safety: every link on the way down is valid, and we have unique access to the tree via `root`.
+    unsafe {
+        let mut node = (*slot)?;
+        while let Some(left) = node.as_ref().left {
+            node.as_mut().size -= 1;
+            parent = Some(node);
+            slot = &mut node.as_mut().left;
+            node = left;
         }
+        *slot = node.as_ref().right;
+        set_parent(node.as_ref().right, parent);
+        let node_ref = node.as_mut();
+        node_ref.right = None;
+        node_ref.size = 1;
+        set_parent(Some(node), None);
+        Some(node)
+    }
+}
 
-        Some(&unsafe { min.as_ref() }.item)
-    } else {
-        None
+/// Join two detached trees, where every item of `left` is no greater than any item of `right`.
+/// Safety: both must be `None` or valid, detached trees.
+unsafe fn concat_node<T>(mut left: Link<T>, right: Link<T>) -> Link<T> {
+    // Safety: guaranteed by the caller.
+    unsafe {
+        match pop_last_node(&mut left) {
+            None => right,
+            Some(pivot) => Some(join_node(left, pivot, right)),
+        }
     }
 }
 
-unsafe fn find_maximum<'a, T>(t: Option<NonNull<Node<T>>>) -> Option<&'a T>
+/// Collect the nodes of a tree in sorted order.
+/// Safety: l must be `None` or point to a valid tree.
+unsafe fn collect_nodes<T>(l: Link<T>, out: &mut Vec<NonNull<Node<T>>>) {
+    if let Some(node) = l {
+        unsafe {
+            collect_nodes(node.as_ref().left, out);
+            out.push(node);
+            collect_nodes(node.as_ref().right, out);
+        }
+    }
+}
+
+/// Link detached nodes, given in sorted order, into a balanced tree.
+/// Safety: the nodes must be valid and detached, and not part of any other tree.
+unsafe fn link_balanced<T>(nodes: &[NonNull<Node<T>>]) -> Link<T> {
+    if nodes.is_empty() {
+        return None;
+    }
+    let mid = nodes.len() / 2;
+    // Safety: guaranteed by the caller.
+    unsafe {
+        let left = link_balanced(&nodes[..mid]);
+        let right = link_balanced(&nodes[mid + 1..]);
+        Some(join_node(left, nodes[mid], right))
+    }
+}
+
+/// Make `node` the root of a tree with `left` and `right` as its subtrees.
+/// Safety: all three must be valid and detached, and `node`'s item must sit between the two trees.
+unsafe fn join_node<T>(
+    left: Link<T>,
+    mut node: NonNull<Node<T>>,
+    right: Link<T>,
+) -> NonNull<Node<T>> {
+    // Safety: guaranteed by the caller.
+    unsafe {
+        let node_ref = node.as_mut();
+        node_ref.left = left;
+        node_ref.right = right;
+        node_ref.size = 1 + size_of(left) + size_of(right);
+        set_parent(left, Some(node));
+        set_parent(right, Some(node));
+        set_parent(Some(node), None);
+    }
+    node
+}
+
+/// Whether an item comes before the start of a range.
+fn before_range<Q, R>(range: &R, item: &Q) -> bool
 where
-    T: Ord,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
 {
-    if let Some(t) = t {
-        let mut max = t;
+    match range.start_bound() {
+        Bound::Included(start) => item < start,
+        Bound::Excluded(start) => item <= start,
+        Bound::Unbounded => false,
+    }
+}
 
-        loop {
-            match unsafe { max.as_ref() }.right {
-                None => break,
-                Some(right) => {
-                    max = right;
-                }
-            }
+/// Whether an item comes after the end of a range.
+fn after_range<Q, R>(range: &R, item: &Q) -> bool
+where
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+{
+    match range.end_bound() {
+        Bound::Included(end) => item > end,
+        Bound::Excluded(end) => item >= end,
+        Bound::Unbounded => false,
+    }
+}
+
+/// Count the items of a tree that satisfy `below`, which must hold for some prefix of
+/// the items in sorted order, and for none of the others.
+/// Safety: l must be `None` or point to a valid tree.
+unsafe fn count_below<T>(l: Link<T>, below: &mut impl FnMut(&T) -> bool) -> usize {
+    let mut count = 0;
+    let mut current = l;
+    while let Some(node) = current {
+        // Safety: guaranteed by the caller.
+        let node = unsafe { node.as_ref() };
+        current = if below(&node.item) {
+            count += unsafe { size_of(node.left) } + 1;
+            node.right
+        } else {
+            node.left
+        };
+    }
+    count
+}
+
+/// Call `f` on every item of a subtree within a range, in sorted order,
+/// skipping over subtrees that lie entirely outside it.
+/// Safety: l must be `None` or point to a valid tree.
+unsafe fn for_each_in_range<'a, T, Q, R, F>(l: Link<T>, range: &R, f: &mut F)
+where
+    T: Borrow<Q> + 'a,
+    Q: Ord + ?Sized,
+    R: RangeBounds<Q>,
+    F: FnMut(&'a T),
+{
+    let Some(node) = l else { return };
+    // Safety: the caller guarantees the tree is valid for 'a.
+    let node = unsafe { node.as_ref() };
+    let before = before_range(range, node.item.borrow());
+    let after = after_range(range, node.item.borrow());
+    unsafe {
+        if !before {
+            for_each_in_range(node.left, range, f);
         }
+        if !before && !after {
+            f(&node.item);
+        }
+        if !after {
+            for_each_in_range(node.right, range, f);
+        }
+    }
+}
 
-        Some(&unsafe { max.as_ref() }.item)
-    } else {
-        None
+/// Follow left children down to the smallest node of a subtree.
+/// Safety: node must point to a valid node.
+unsafe fn leftmost<T>(mut node: NonNull<Node<T>>) -> NonNull<Node<T>> {
+    while let Some(left) = unsafe { node.as_ref() }.left {
+        node = left;
     }
+    node
 }
 
-impl<T> Default for BinarySearchTree<T> {
-    fn default() -> Self {
-        Self::new()
+/// Follow right children down to the largest node of a subtree.
+/// Safety: node must point to a valid node.
+unsafe fn rightmost<T>(mut node: NonNull<Node<T>>) -> NonNull<Node<T>> {
+    while let Some(right) = unsafe { node.as_ref() }.right {
+        node = right;
     }
+    node
 }
 
-impl<'a, T> BinarySearchTree<T> {
-    pub fn new() -> Self {
+/// A node of a tree, along with whatever is needed to step to its neighbours.
+/// With parent pointers that is nothing, otherwise we keep the path from the root.
+struct Position<T> {
+    node: Link<T>,
+    /// Ancestors of `node`, from the root down.
+    #[cfg(not(feature = "parent-pointers"))]
+    ancestors: Vec<NonNull<Node<T>>>,
+}
+
+impl<T> Clone for Position<T> {
+    fn clone(&self) -> Self {
         Self {
-            root: None,
-            _marker: PhantomData,
+            node: self.node,
+            #[cfg(not(feature = "parent-pointers"))]
+            ancestors: self.ancestors.clone(),
         }
     }
+}
 
-    pub fn insert(&mut self, value: T)
-    where
-        T: Ord,
-    {
-        unsafe {
-            if let Some(root) = self.root {
-                insert_node(&mut Some(root), value, None);
-            } else {
-                // Safety: Box::into_raw is never null.
-                let root_ptr = NonNull::new_unchecked(Box::into_raw(Box::new(Node::new(value))));
-                self.root = Some(root_ptr);
+impl<T> Position<T> {
+    /// Position at the root of a tree.
+    fn root(root: Link<T>) -> Self {
+        Self {
+            node: root,
+            #[cfg(not(feature = "parent-pointers"))]
+            ancestors: Vec::new(),
+        }
+    }
+
+    /// Position at the smallest node of a tree.
+    /// Safety: root must be `None` or point to a valid tree.
+    unsafe fn first(root: Link<T>) -> Self {
+        let mut position = Self::root(root);
+        while let Some(left) = position.node.and_then(|node| unsafe { node.as_ref() }.left) {
+            position.descend(left);
+        }
+        position
+    }
+
+    /// Position at the largest node of a tree.
+    /// Safety: root must be `None` or point to a valid tree.
+    unsafe fn last(root: Link<T>) -> Self {
+        let mut position = Self::root(root);
+        while let Some(right) = position
+            .node
+            .and_then(|node| unsafe { node.as_ref() }.right)
+        {
+            position.descend(right);
+        }
+        position
+    }
+
+    /// Position at the node with the given rank in sorted order, or off the end of the tree
+    /// if there are not that many nodes. Links are read as in `move_next`.
+    /// Safety: root must be `None` or point to a valid tree.
+    unsafe fn at_rank(root: Link<T>, mut rank: usize) -> Self {
+        let mut position = Self::root(root);
+        while let Some(node) = position.node {
+            let (left, right) = unsafe { ((*node.as_ptr()).left, (*node.as_ptr()).right) };
+            let left_size = unsafe { size_of(left) };
+            let child = match rank.cmp(&left_size) {
+                std::cmp::Ordering::Equal => return position,
+                std::cmp::Ordering::Less => left,
+                std::cmp::Ordering::Greater => {
+                    rank -= left_size + 1;
+                    right
+                }
+            };
+            match child {
+                Some(child) => position.descend(child),
+                None => break,
             }
         }
+        Self::root(None)
     }
 
-    pub fn get<Q>(&'a self, item: &Q) -> Option<&'a T>
-    where
-        T: Borrow<Q> + Ord,
-        Q: Ord + ?Sized,
-    {
-        if let Some(root) = self.root {
-            unsafe {
-                let (_, node) = search_node(Some(root), item, true);
-                node.map(|ptr| ptr.as_ref().item())
+    fn node(&self) -> Link<T> {
+        self.node
+    }
+
+    /// Number of ancestors of the current node, which is its depth in the tree.
+    /// Safety: the current node must be valid and belong to a well-formed tree.
+    unsafe fn depth(&self) -> usize {
+        #[cfg(feature = "parent-pointers")]
+        {
+            let mut depth = 0;
+            let mut current = self.node;
+            while let Some(parent) = current.and_then(|node| unsafe { node.as_ref() }.parent) {
+                depth += 1;
+                current = Some(parent);
             }
-        } else {
-            None
+            depth
         }
+        #[cfg(not(feature = "parent-pointers"))]
+        self.ancestors.len()
     }
 
-    pub fn contains<Q>(&'a self, item: &Q) -> bool
-    where
-        T: Borrow<Q> + Ord,
-        Q: Ord + ?Sized,
-    {
-        self.get(item).is_some()
+    /// Move down to a child of the current node.
+    fn descend(&mut self, child: NonNull<Node<T>>) {
+        #[cfg(not(feature = "parent-pointers"))]
+        self.ancestors.extend(self.node);
+        self.node = Some(child);
     }
 
-    pub fn min(&self) -> Option<&T>
-    where
-        T: Ord,
-    {
-        if let Some(root) = self.root {
-            unsafe { find_minimum(Some(root)) }
-        } else {
-            None
+    /// Move up to the parent of the current node, returning it.
+    /// Safety: the current node must be valid.
+    unsafe fn ascend(&mut self) -> Link<T> {
+        #[cfg(feature = "parent-pointers")]
+        {
+            self.node = self
+                .node
+                .and_then(|node| unsafe { (*node.as_ptr()).parent });
+        }
+        #[cfg(not(feature = "parent-pointers"))]
+        {
+            self.node = self.ancestors.pop();
         }
+        self.node
     }
 
-    pub fn max(&self) -> Option<&T>
-    where
-        T: Ord,
-    {
-        if let Some(root) = self.root {
-            unsafe { find_maximum(Some(root)) }
-        } else {
-            None
+    /// Move to the next node in sorted order, or off the end of the tree.
+    /// Links are read straight through the node pointers, without borrowing whole nodes,
+    /// so this is fine while items of nodes already passed are mutably borrowed.
+    /// Safety: the current node must be valid and belong to a well-formed tree.
+    unsafe fn move_next(&mut self) {
+        let Some(node) = self.node else { return };
+        unsafe {
+            if let Some(right) = (*node.as_ptr()).right {
+                self.descend(right);
+                while let Some(left) = (*self.node.unwrap_unchecked().as_ptr()).left {
+                    self.descend(left);
+                }
+                return;
+            }
+
+            let mut child = node;
+            while let Some(parent) = self.ascend() {
+                if (*parent.as_ptr()).left == Some(child) {
+                    return;
+                }
+                child = parent;
+            }
         }
     }
 
-    pub fn delete<Q>(&mut self, item: &Q)
-    where
-        T: Borrow<Q> + Ord,
-        Q: Ord + ?Sized,
-    {
-        if let Some(root) = self.root {
-            unsafe {
-                let (is_root, node) = search_node(Some(root), item, true);
-                if let Some(mut ptr) = node {
-                    let was_leaf = delete_node(Some(&mut ptr));
-                    // if the deleted node was the last node, change root to NULL.
-                    // Only the last node if it was the root node, and it had no children.
-
-                    if is_root && was_leaf {
-                        self.root = None;
-                    }
+    /// Move to the previous node in sorted order, or off the start of the tree.
+    /// Links are read as in `move_next`.
+    /// Safety: the current node must be valid and belong to a well-formed tree.
+    unsafe fn move_prev(&mut self) {
+        let Some(node) = self.node else { return };
+        unsafe {
+            if let Some(left) = (*node.as_ptr()).left {
+                self.descend(left);
+                while let Some(right) = (*self.node.unwrap_unchecked().as_ptr()).right {
+                    self.descend(right);
+                }
+                return;
+            }
+
+            let mut child = node;
+            while let Some(parent) = self.ascend() {
+                if (*parent.as_ptr()).right == Some(child) {
+                    return;
                 }
+                child = parent;
+            }
+        }
+    }
+}
+
+unsafe fn find_minimum<'a, T>(t: Link<T>) -> Option<&'a T>
+where
+    T: Ord,
+{
+    t.map(|t| unsafe { &leftmost(t).as_ref().item })
+}
+
+unsafe fn find_maximum<'a, T>(t: Link<T>) -> Option<&'a T>
+where
+    T: Ord,
+{
+    t.map(|t| unsafe { &rightmost(t).as_ref().item })
+}
+
+/// Copy a tree, keeping its shape and node IDs. Nodes are taken from `spare`, with their items
+/// overwritten using `clone_from`, until it runs out, then allocated from `nodes`.
+/// Safety: src must be `None` or a valid tree, and the spare nodes must be valid,
+/// detached and belong to `nodes`.
+unsafe fn copy_node<T: Clone>(
+    nodes: &mut NodeAlloc<T>,
+    spare: &mut Vec<NonNull<Node<T>>>,
+    src: Link<T>,
+) -> Link<T> {
+    // Safety: guaranteed by the caller.
+    unsafe {
+        let src = src?.as_ref();
+        let left = copy_node(nodes, spare, src.left);
+        let right = copy_node(nodes, spare, src.right);
+        let mut node = match spare.pop() {
+            Some(mut node) => {
+                node.as_mut().item.clone_from(&src.item);
+                node
             }
+            None => nodes.alloc(src.item.clone()),
+        };
+        let node_ref = node.as_mut();
+        #[cfg(feature = "node-ids")]
+        {
+            node_ref.id = src.id;
         }
+        node_ref.left = left;
+        node_ref.right = right;
+        node_ref.size = src.size;
+        set_parent(left, Some(node));
+        set_parent(right, Some(node));
+        set_parent(Some(node), None);
+        Some(node)
+    }
+}
+
+/// Move every item of a subtree into `out` in sorted order, deallocating nodes as it goes.
+/// Safety: same requirements as `dispose_node`.
+unsafe fn drain_node<T>(nodes: &mut NodeAlloc<T>, l: NonNull<Node<T>>, out: &mut Vec<T>) {
+    let (left, right) = unsafe { (l.as_ref().left, l.as_ref().right) };
+    if let Some(left) = left {
+        unsafe { drain_node(nodes, left, out) };
+    }
+    // Safety: l came from `nodes` and is not aliased, so we can take back ownership of it.
+    out.push(unsafe { nodes.free(l) });
+    if let Some(right) = right {
+        unsafe { drain_node(nodes, right, out) };
+    }
+}
+
+/// Build a balanced subtree out of the next `len` items of a sorted iterator.
+fn build_balanced<T, I>(nodes: &mut NodeAlloc<T>, items: &mut I, len: usize) -> Link<T>
+where
+    I: Iterator<Item = T>,
+{
+    if len == 0 {
+        return None;
+    }
+
+    let left_len = len / 2;
+    let left = build_balanced(nodes, items, left_len);
+    let item = items
+        .next()
+        .expect("iterator yielded fewer items than its length");
+    let right = build_balanced(nodes, items, len - left_len - 1);
+
+    let mut node = nodes.alloc(item);
+
+    // Safety: the node and its children were just allocated by us and nobody else points at them.
+    unsafe {
+        let node_ref = node.as_mut();
+        node_ref.left = left;
+        node_ref.right = right;
+        node_ref.size = len;
+        set_parent(left, Some(node));
+        set_parent(right, Some(node));
+    }
+
+    Some(node)
+}
+
+impl<T> Default for BinarySearchTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> Extend<T> for BinarySearchTree<T> {
+    /// Add the items as if inserting them one by one, following the duplicate policy.
+    /// They are sorted and built into a balanced tree of their own, which is then merged in
+    /// like `append` does: joined on if it doesn't overlap this tree, and otherwise merged
+    /// in order and relinked into a balanced tree, in time linear in the sizes of both.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let mut items: Vec<T> = iter.into_iter().collect();
+        // The sort is stable, so equal items stay in the order they would have been inserted.
+        items.sort();
+        let len = items.len();
+        let theirs = build_balanced(&mut self.nodes, &mut items.into_iter(), len);
+        // Safety: the new nodes came from our allocator, and are not part of any tree yet.
+        unsafe { self.merge_detached(theirs, self.duplicates == DuplicatePolicy::Allow) };
+    }
+}
+
+impl<'a, T> Extend<&'a T> for BinarySearchTree<T>
+where
+    T: Ord + Clone + 'a,
+{
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().cloned());
+    }
+}
+
+impl<T> IntoIterator for BinarySearchTree<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter::new(self)
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BinarySearchTree<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<T> SubAssign<&BinarySearchTree<T>> for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    fn sub_assign(&mut self, other: &BinarySearchTree<T>) {
+        self.subtract(other);
+    }
+}
+
+impl<T: Clone> Clone for BinarySearchTree<T> {
+    /// Copy every node, keeping the tree's shape.
+    fn clone(&self) -> Self {
+        let mut tree = self.empty_like();
+        // Safety: our tree is borrowed for the whole copy, and the new one is empty.
+        unsafe {
+            tree.root = copy_node(&mut tree.nodes, &mut Vec::new(), self.root);
+            #[cfg(feature = "node-ids")]
+            tree.nodes.resume_ids(tree.root);
+        }
+        tree
+    }
+
+    /// Copy `source`'s nodes into this tree, keeping its shape. Our own nodes are reused,
+    /// along with whatever their items own, and only the shortfall is allocated.
+    fn clone_from(&mut self, source: &Self) {
+        self.duplicates = source.duplicates;
+        let mut spare = Vec::with_capacity(self.len());
+        // Safety: we have unique access to this tree, and every node we collect is either
+        // relinked into the copy or freed.
+        unsafe {
+            collect_nodes(self.root.take(), &mut spare);
+            self.root = copy_node(&mut self.nodes, &mut spare, source.root);
+            for node in spare {
+                self.nodes.free(node);
+            }
+            #[cfg(feature = "node-ids")]
+            self.nodes.resume_ids(self.root);
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for BinarySearchTree<T> {
+    /// Trees are equal if they hold equal items, whatever their shapes.
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<T: Eq> Eq for BinarySearchTree<T> {}
+
+impl<T> Index<usize> for BinarySearchTree<T> {
+    type Output = T;
+
+    /// The item at a rank, counting from 0, as found by `select`.
+    /// Panics if there aren't that many items.
+    fn index(&self, rank: usize) -> &T {
+        match self.select(rank) {
+            Some(item) => item,
+            None => panic!(
+                "rank {rank} is out of bounds for a tree of {} items",
+                self.len()
+            ),
+        }
+    }
+}
+
+impl<T: Hash> Hash for BinarySearchTree<T> {
+    /// Hashes the items in sorted order, so trees that are equal hash the same.
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for item in self.iter() {
+            item.hash(state);
+        }
+    }
+}
+
+// There's deliberately no `Ord`, as its `min` and `max` methods take `self` by value, so would
+// be picked over the tree's own `min` and `max` wherever a tree is called on directly.
+impl<T: PartialOrd> PartialOrd for BinarySearchTree<T> {
+    /// Trees are compared by their items in sorted order, like `BTreeSet`s.
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<T: Ord> FromIterator<T> for BinarySearchTree<T> {
+    /// Sort the items, then build them into a balanced tree.
+    /// Equal items keep the order they came in, just as if they had been inserted one by one.
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut items = Vec::from_iter(iter);
+        items.sort();
+        Self::from_sorted_vec(items)
+    }
+}
+
+impl<T: Ord> From<Vec<T>> for BinarySearchTree<T> {
+    fn from(items: Vec<T>) -> Self {
+        Self::from_iter(items)
+    }
+}
+
+impl<T: Ord, const N: usize> From<[T; N]> for BinarySearchTree<T> {
+    fn from(items: [T; N]) -> Self {
+        Self::from_iter(items)
+    }
+}
+
+impl<T: Ord + Clone> From<&[T]> for BinarySearchTree<T> {
+    fn from(items: &[T]) -> Self {
+        items.iter().cloned().collect()
+    }
+}
+
+impl<T> From<BinaryHeap<T>> for BinarySearchTree<T>
+where
+    T: Ord,
+{
+    fn from(heap: BinaryHeap<T>) -> Self {
+        Self::from_sorted_vec(heap.into_sorted_vec())
+    }
+}
+
+impl<T> From<BinarySearchTree<T>> for BinaryHeap<T>
+where
+    T: Ord,
+{
+    fn from(tree: BinarySearchTree<T>) -> Self {
+        BinaryHeap::from(tree.into_sorted_vec())
+    }
+}
+
+impl<T> From<BTreeSet<T>> for BinarySearchTree<T> {
+    fn from(set: BTreeSet<T>) -> Self {
+        Self::from_sorted_vec(set.into_iter().collect())
+    }
+}
+
+/// Equal items in the tree are collapsed into one.
+impl<T> From<BinarySearchTree<T>> for BTreeSet<T>
+where
+    T: Ord,
+{
+    fn from(tree: BinarySearchTree<T>) -> Self {
+        // Already sorted, so the set is built in linear time.
+        tree.into_sorted_vec().into_iter().collect()
+    }
+}
+
+impl<T> From<BinarySearchTree<T>> for Vec<T> {
+    fn from(tree: BinarySearchTree<T>) -> Self {
+        tree.into_sorted_vec()
+    }
+}
+
+impl<'a, T> BinarySearchTree<T> {
+    pub fn new() -> Self {
+        Self::with_nodes(NodeAlloc::global())
+    }
+
+    /// Create a tree whose nodes are carved out of large chunks of memory,
+    /// which are freed all at once when the tree is dropped.
+    /// This makes building up and throwing away short-lived trees a lot cheaper.
+    pub fn new_pooled() -> Self {
+        Self::with_nodes(NodeAlloc::pooled())
+    }
+
+    /// Set what to do with items equal to ones already in the tree, from now on. If the tree
+    /// already holds equal items that the policy doesn't allow, only one of each run of them
+    /// is kept: the first with `Reject`, or the last with `Replace`. Trees start out with
+    /// `DuplicatePolicy::Allow`, and trees split off this one or cloned from it get the same policy.
+    pub fn with_duplicates(mut self, policy: DuplicatePolicy) -> Self
+    where
+        T: Ord,
+    {
+        self.duplicates = policy;
+        if policy != DuplicatePolicy::Allow && self.iter_pairs().any(|(a, b)| a.cmp(b).is_eq()) {
+            let mut nodes = Vec::with_capacity(self.len());
+            // Safety: we own the tree, and relink every node we keep back into it.
+            unsafe {
+                collect_nodes(self.root.take(), &mut nodes);
+                self.dedup_nodes(&mut nodes);
+                self.root = link_balanced(&nodes);
+            }
+            #[cfg(feature = "stats")]
+            self.stats.record_rebalance();
+        }
+        self
+    }
+
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicates
+    }
+
+    /// Whether `after` can come straight after `before` in the tree, which needs them to be
+    /// in order, and if the duplicate policy doesn't allow duplicates, not equal.
+    fn in_order(&self, before: &T, after: &T) -> bool
+    where
+        T: Ord,
+    {
+        match self.duplicates {
+            DuplicatePolicy::Allow => before <= after,
+            DuplicatePolicy::Reject | DuplicatePolicy::Replace => before < after,
+        }
+    }
+
+    /// Whether items from `other` are free of any duplicates among themselves that this tree's
+    /// duplicate policy wouldn't allow, so can be linked in without checking each one.
+    fn accepts_all_of(&self, other: &Self) -> bool {
+        self.duplicates == DuplicatePolicy::Allow || other.duplicates != DuplicatePolicy::Allow
+    }
+
+    /// Free all but one of each run of equal nodes, as the duplicate policy says: the first
+    /// with `Reject`, or the last with `Replace`. Does nothing with `Allow`.
+    /// Safety: the nodes must be in sorted order, with equal ones in the order they were added,
+    /// come from this tree's allocator, and be detached, as the ones kept will be relinked.
+    unsafe fn dedup_nodes(&mut self, nodes: &mut Vec<NonNull<Node<T>>>)
+    where
+        T: Ord,
+    {
+        let keep_last = match self.duplicates {
+            DuplicatePolicy::Allow => return,
+            DuplicatePolicy::Reject => false,
+            DuplicatePolicy::Replace => true,
+        };
+        let mut kept: Vec<NonNull<Node<T>>> = Vec::with_capacity(nodes.len());
+        for node in nodes.drain(..) {
+            // Safety: guaranteed by the caller.
+            unsafe {
+                match kept.last_mut() {
+                    Some(last) if last.as_ref().item.cmp(&node.as_ref().item).is_eq() => {
+                        let dropped = if keep_last {
+                            std::mem::replace(last, node)
+                        } else {
+                            node
+                        };
+                        drop(self.nodes.free(dropped));
+                    }
+                    _ => kept.push(node),
+                }
+            }
+        }
+        *nodes = kept;
+    }
+
+    /// Link a detached node into the tree after any equal items, or if the duplicate policy
+    /// doesn't allow that, free it, having first moved its item into the equal node's place
+    /// with `Replace`.
+    /// Safety: the node must come from this tree's allocator and be detached.
+    unsafe fn insert_detached(&mut self, mut node: NonNull<Node<T>>)
+    where
+        T: Ord,
+    {
+        // Safety: guaranteed by the caller, and we have unique access to the tree.
+        unsafe {
+            let node_ref = node.as_mut();
+            node_ref.left = None;
+            node_ref.right = None;
+            node_ref.size = 1;
+            if self.duplicates != DuplicatePolicy::Allow {
+                let (found, _) = search_path_by(self.root, |item: &T| node_ref.item.cmp(item));
+                if let Some(mut found) = found {
+                    if self.duplicates == DuplicatePolicy::Replace {
+                        std::mem::swap(&mut found.as_mut().item, &mut node_ref.item);
+                    }
+                    drop(self.nodes.free(node));
+                    return;
+                }
+            }
+            insert_node(&mut self.root, node);
+        }
+    }
+
+    /// An empty tree with the same kind of allocator and duplicate policy as this one.
+    fn empty_like(&self) -> Self {
+        let mut tree = Self::with_nodes(self.nodes.new_like());
+        tree.duplicates = self.duplicates;
+        tree
+    }
+
+    fn with_nodes(nodes: NodeAlloc<T>) -> Self {
+        Self {
+            root: None,
+            nodes,
+            duplicates: DuplicatePolicy::Allow,
+            #[cfg(feature = "stats")]
+            stats: Default::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Split the tree into its root's left subtree, the root item, and its right subtree.
+    /// Returns `None` if the tree is empty.
+    pub fn into_parts(mut self) -> Option<(Self, T, Self)> {
+        let root = self.root.take()?;
+        // Safety: we own the whole tree, and the root is never used after it is freed.
+        unsafe {
+            let (left, right) = (root.as_ref().left, root.as_ref().right);
+            set_parent(left, None);
+            set_parent(right, None);
+            let item = self.nodes.free(root);
+
+            let mut right_tree = self.empty_like();
+            right_tree.root = right_tree.nodes.adopt(&mut self.nodes, right);
+            self.root = left;
+            Some((self, item, right_tree))
+        }
+    }
+
+    /// Build a tree out of `item`, and the items of two trees smaller and larger than it.
+    /// The result's nodes come from `left`'s allocator, so if either tree is pooled, `right`'s
+    /// nodes have to be moved over one by one. Otherwise this takes time proportional to the
+    /// heights of the two trees, spent checking that their items are in order around `item`.
+    ///
+    /// The result has `left`'s duplicate policy. If that doesn't allow equal items, any among
+    /// the three are dealt with as if `item` and then `right`'s items had been inserted into
+    /// `left`, which means merging all the nodes and relinking them into a balanced tree.
+    ///
+    /// Panics if `left` has an item greater than `item`, or `right` has one less than it.
+    pub fn join(mut left: Self, item: T, mut right: Self) -> Self
+    where
+        T: Ord,
+    {
+        assert!(
+            left.max().is_none_or(|max| *max <= item),
+            "left tree has items greater than the joining item"
+        );
+        assert!(
+            right.min().is_none_or(|min| item <= *min),
+            "right tree has items less than the joining item"
+        );
+        let linkable = left.accepts_all_of(&right)
+            && left.max().is_none_or(|max| left.in_order(max, &item))
+            && right.min().is_none_or(|min| left.in_order(&item, min));
+
+        // Safety: both trees are owned by us, and their items are in order around `item`.
+        unsafe {
+            let right_root = left.nodes.adopt(&mut right.nodes, right.root.take());
+            let node = left.nodes.alloc(item);
+            if linkable {
+                left.root = Some(join_node(left.root, node, right_root));
+            } else {
+                let mut nodes = Vec::with_capacity(left.len() + 1 + size_of(right_root));
+                collect_nodes(left.root.take(), &mut nodes);
+                nodes.push(node);
+                collect_nodes(right_root, &mut nodes);
+                left.dedup_nodes(&mut nodes);
+                left.root = link_balanced(&nodes);
+                #[cfg(feature = "stats")]
+                left.stats.record_rebalance();
+            }
+        }
+        left
+    }
+
+    /// Split the tree into the items less than `key`, an item equal to it (if there is one),
+    /// and the items greater than it. Any other items equal to `key` end up on the right.
+    /// Takes time proportional to the height of the tree.
+    pub fn split<Q>(mut self, key: &Q) -> (Self, Option<T>, Self)
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        // Safety: we own the whole tree, so can take it apart and hand the pieces to new trees.
+        unsafe {
+            let (left, mut right) =
+                split_node(self.root.take(), &mut |item: &T| item.borrow() < key);
+            let item = remove_node(&mut self.nodes, &mut right, key);
+
+            let mut right_tree = self.empty_like();
+            right_tree.root = right_tree.nodes.adopt(&mut self.nodes, right);
+            self.root = left;
+            (self, item, right_tree)
+        }
+    }
+
+    /// Move every item not less than `key` into a new tree, leaving the smaller ones behind.
+    /// Subtrees are relinked rather than copied, so this takes time proportional to the
+    /// height of the tree, unless it is pooled.
+    pub fn split_off<Q>(&mut self, key: &Q) -> Self
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        // Safety: we have unique access to the tree, and hand every node on to one of the two trees.
+        unsafe {
+            let (left, right) = split_node(self.root.take(), &mut |item: &T| item.borrow() < key);
+            self.root = left;
+            let mut right_tree = self.empty_like();
+            right_tree.root = right_tree.nodes.adopt(&mut self.nodes, right);
+            right_tree
+        }
+    }
+
+    /// Move every item of `other` into this tree, leaving `other` empty. Items of `other` equal
+    /// to items of ours, or to each other, are dealt with by our duplicate policy, as if they
+    /// had been inserted one by one.
+    /// If the two trees' items don't overlap, they are joined in time proportional to their
+    /// heights. Otherwise, the nodes of both are merged in order and relinked into a balanced tree.
+    pub fn append(&mut self, other: &mut Self)
+    where
+        T: Ord,
+    {
+        let linkable = self.accepts_all_of(other);
+        // Safety: we have unique access to both trees, and the nodes we adopt are detached.
+        unsafe {
+            let theirs = self.nodes.adopt(&mut other.nodes, other.root.take());
+            self.merge_detached(theirs, linkable);
+        }
+    }
+
+    /// Move the items of a detached tree into this one, as `append` does. `linkable` says
+    /// whether they are free of duplicates among themselves that our policy wouldn't allow.
+    /// Safety: theirs must be `None` or a valid, detached tree whose nodes came from our allocator.
+    unsafe fn merge_detached(&mut self, theirs: Link<T>, linkable: bool)
+    where
+        T: Ord,
+    {
+        if theirs.is_none() {
+            return;
+        }
+        // Safety: guaranteed by the caller, and we relink every node into this tree.
+        unsafe {
+            if linkable {
+                let (Some(ours), Some(theirs)) = (self.root, theirs) else {
+                    self.root = self.root.or(theirs);
+                    return;
+                };
+
+                if self.in_order(
+                    &rightmost(ours).as_ref().item,
+                    &leftmost(theirs).as_ref().item,
+                ) {
+                    self.root = concat_node(Some(ours), Some(theirs));
+                    return;
+                }
+                if rightmost(theirs).as_ref().item < leftmost(ours).as_ref().item {
+                    self.root = concat_node(Some(theirs), Some(ours));
+                    return;
+                }
+            }
+
+            let mut our_nodes = Vec::with_capacity(self.len());
+            let mut their_nodes = Vec::with_capacity(size_of(theirs));
+            collect_nodes(self.root.take(), &mut our_nodes);
+            collect_nodes(theirs, &mut their_nodes);
+
+            let mut merged = Vec::with_capacity(our_nodes.len() + their_nodes.len());
+            let mut their_nodes = their_nodes.into_iter().peekable();
+            for node in our_nodes {
+                while let Some(next) =
+                    their_nodes.next_if(|next| next.as_ref().item < node.as_ref().item)
+                {
+                    merged.push(next);
+                }
+                merged.push(node);
+            }
+            merged.extend(their_nodes);
+            self.dedup_nodes(&mut merged);
+            self.root = link_balanced(&merged);
+        }
+        #[cfg(feature = "stats")]
+        self.stats.record_rebalance();
+    }
+
+    /// Whether any item lies within a range. Only walks one path down the tree.
+    pub fn contains_range<Q, R>(&self, range: R) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut current = self.root;
+        while let Some(node) = current {
+            // Safety: the node belongs to this tree, which we are borrowing.
+            let node = unsafe { node.as_ref() };
+            current = if before_range(&range, node.item.borrow()) {
+                node.right
+            } else if after_range(&range, node.item.borrow()) {
+                node.left
+            } else {
+                return true;
+            };
+        }
+        false
+    }
+
+    /// Iterate over the items within a range, in sorted order.
+    /// Jumps straight to either end of the range rather than walking up to it.
+    pub fn range<Q, R>(&self, range: R) -> Iter<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        // Safety: the tree is borrowed for as long as the iterator.
+        unsafe {
+            let start = count_below(self.root, &mut |item: &T| {
+                before_range(&range, item.borrow())
+            });
+            let end = count_below(self.root, &mut |item: &T| {
+                !after_range(&range, item.borrow())
+            });
+            Iter::from_ranks(self.root, start, end)
+        }
+    }
+
+    /// Count the items within a range, using subtree sizes to skip over everything in between,
+    /// so it only walks the paths down to either end of the range.
+    pub fn range_count<Q, R>(&self, range: R) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        // Safety: the tree is borrowed for the duration of the walks.
+        unsafe {
+            let start = count_below(self.root, &mut |item: &T| {
+                before_range(&range, item.borrow())
+            });
+            let end = count_below(self.root, &mut |item: &T| {
+                !after_range(&range, item.borrow())
+            });
+            end.saturating_sub(start)
+        }
+    }
+
+    /// Iterate over the items not less than `key`, in sorted order.
+    pub fn iter_from<Q>(&self, key: &Q) -> Iter<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.range((Bound::Included(key), Bound::Unbounded))
+    }
+
+    /// Iterate over the items greater than `key`, in sorted order.
+    pub fn iter_after<Q>(&self, key: &Q) -> Iter<'_, T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.range((Bound::Excluded(key), Bound::Unbounded))
+    }
+
+    /// Copy the items within a range into a new, balanced tree.
+    /// Only visits the nodes in the range, plus the paths down to its ends.
+    pub fn clone_range<Q, R>(&self, range: R) -> Self
+    where
+        T: Borrow<Q> + Clone,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let mut items = Vec::new();
+        // Safety: the tree is borrowed for the duration of the walk.
+        unsafe { for_each_in_range(self.root, &range, &mut |item: &T| items.push(item.clone())) };
+        let mut tree = self.empty_like();
+        let len = items.len();
+        tree.root = build_balanced(&mut tree.nodes, &mut items.into_iter(), len);
+        tree
+    }
+
+    /// Move the items within a range out of this tree and into `other`, returning how many moved.
+    /// Items equal to ones already in `other`, or to each other, are dealt with by `other`'s
+    /// duplicate policy, as if they had been inserted one by one.
+    /// Nodes are relinked rather than reallocated, unless either tree is pooled.
+    pub fn splice_range<Q, R>(&mut self, range: R, other: &mut Self) -> usize
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let within = self.take_range(range);
+        let linkable = other.accepts_all_of(self);
+        // Safety: we have unique access to both trees, and only ever move whole nodes between them.
+        unsafe {
+            let mut within = other.nodes.adopt(&mut self.nodes, within);
+            let moved = size_of(within);
+
+            // If the moved items all go past one end of the other tree, it's a cheap join.
+            let below = find_maximum(within)
+                .zip(other.min())
+                .is_some_and(|(max, min)| other.in_order(max, min));
+            let above = find_minimum(within)
+                .zip(other.max())
+                .is_some_and(|(min, max)| other.in_order(max, min));
+            if !linkable {
+                let mut nodes = Vec::with_capacity(moved);
+                collect_nodes(within, &mut nodes);
+                for node in nodes {
+                    other.insert_detached(node);
+                }
+            } else if other.root.is_none() {
+                other.root = within;
+            } else if above {
+                let pivot = pop_first_node(&mut within).unwrap_unchecked();
+                other.root = Some(join_node(other.root, pivot, within));
+            } else if below {
+                let pivot = pop_last_node(&mut within).unwrap_unchecked();
+                other.root = Some(join_node(within, pivot, other.root));
+            } else {
+                let mut nodes = Vec::with_capacity(moved);
+                collect_nodes(within, &mut nodes);
+                for node in nodes {
+                    other.insert_detached(node);
+                }
+            }
+            moved
+        }
+    }
+
+    /// Remove and iterate over the items within a range, in sorted order.
+    /// The range is cut out of the tree up front, so the tree is left intact even if the
+    /// iterator is leaked, and any items not iterated over are dropped along with it.
+    pub fn drain_range<Q, R>(&mut self, range: R) -> DrainRange<'_, T>
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let within = self.take_range(range);
+        DrainRange::new(&mut self.nodes, within)
+    }
+
+    /// Remove the items within a range, returning how many there were.
+    /// The range is cut out of the tree in one go, and the rest joined back together.
+    pub fn remove_range<Q, R>(&mut self, range: R) -> usize
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let within = self.take_range(range);
+        // Safety: the nodes were cut out of the tree, and are never used again.
+        unsafe {
+            let removed = size_of(within);
+            self.nodes.free_tree(within);
+            removed
+        }
+    }
+
+    /// Remove and iterate over every item, in sorted order, leaving the tree empty.
+    /// Nodes are freed as their items are taken, like `drain_range(..)`.
+    pub fn drain(&mut self) -> DrainRange<'_, T> {
+        DrainRange::new(&mut self.nodes, self.root.take())
+    }
+
+    /// Drop every item, leaving the tree empty.
+    /// A pooled tree keeps its node slots, ready to be reused.
+    pub fn clear(&mut self) {
+        // Safety: the nodes are detached from the tree, and never used again.
+        unsafe { self.nodes.free_tree(self.root.take()) };
+    }
+
+    /// Remove and iterate over the items for which `pred` returns true, in sorted order.
+    /// Items are only looked at as the iterator advances, so if it is dropped early,
+    /// the rest of the tree is left as it was. Moving past an item takes amortized constant
+    /// time, and removing one takes time proportional to the height of the tree.
+    pub fn extract_if<F>(&mut self, pred: F) -> ExtractIf<'_, T, F>
+    where
+        F: FnMut(&T) -> bool,
+    {
+        ExtractIf::new(self, pred)
+    }
+
+    /// Cut the items within a range out of the tree, handing back ownership of them as a
+    /// detached tree whose nodes still belong to our allocator.
+    fn take_range<Q, R>(&mut self, range: R) -> Link<T>
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        // Safety: we have unique access to the tree, and relink every node outside the range.
+        unsafe {
+            let (before, rest) = split_node(self.root.take(), &mut |item: &T| {
+                before_range(&range, item.borrow())
+            });
+            let (within, after) =
+                split_node(rest, &mut |item: &T| !after_range(&range, item.borrow()));
+            self.root = concat_node(before, after);
+            within
+        }
+    }
+
+    /// Keep only the items for which `f` returns true, visiting each item once in sorted order.
+    /// The surviving nodes are then relinked into a balanced tree.
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        let mut nodes = Vec::with_capacity(self.len());
+        // Safety: we have unique access to the tree, and relink every node we don't free.
+        unsafe {
+            collect_nodes(self.root.take(), &mut nodes);
+            nodes.retain(|&node| {
+                if f(&node.as_ref().item) {
+                    true
+                } else {
+                    self.nodes.free(node);
+                    false
+                }
+            });
+            self.root = link_balanced(&nodes);
+        }
+        #[cfg(feature = "stats")]
+        self.stats.record_rebalance();
+    }
+
+    /// Remove every item that is equal to an item of `other`.
+    /// Walks both trees side by side once, then relinks the surviving nodes into a balanced tree.
+    pub fn subtract(&mut self, other: &Self)
+    where
+        T: Ord,
+    {
+        if other.root.is_none() {
+            return;
+        }
+
+        let mut nodes = Vec::with_capacity(self.len());
+        // Safety: we have unique access to the tree, and relink every node we don't free.
+        unsafe {
+            collect_nodes(self.root.take(), &mut nodes);
+            let mut theirs = other.iter().peekable();
+            nodes.retain(|&node| {
+                let item = &node.as_ref().item;
+                while theirs.next_if(|&other| other < item).is_some() {}
+                if theirs.peek() == Some(&item) {
+                    self.nodes.free(node);
+                    false
+                } else {
+                    true
+                }
+            });
+            self.root = link_balanced(&nodes);
+        }
+        #[cfg(feature = "stats")]
+        self.stats.record_rebalance();
+    }
+
+    /// Keep only the items that are equal to an item of `other`.
+    /// Subtrees lying entirely outside `other`'s range are cut off without being walked,
+    /// then the rest is compared against `other` side by side.
+    pub fn retain_common(&mut self, other: &Self)
+    where
+        T: Ord,
+    {
+        let (Some(min), Some(max)) = (other.min(), other.max()) else {
+            // Safety: we are throwing away the whole tree.
+            unsafe { self.nodes.free_tree(self.root.take()) };
+            return;
+        };
+
+        // Safety: we have unique access to the tree, and relink every node we don't free.
+        unsafe {
+            let (below, rest) = split_node(self.root.take(), &mut |item: &T| item < min);
+            let (within, above) = split_node(rest, &mut |item: &T| item <= max);
+            self.nodes.free_tree(below);
+            self.nodes.free_tree(above);
+
+            let mut nodes = Vec::with_capacity(size_of(within));
+            collect_nodes(within, &mut nodes);
+            let mut theirs = other.iter().peekable();
+            nodes.retain(|&node| {
+                let item = &node.as_ref().item;
+                while theirs.next_if(|&other| other < item).is_some() {}
+                if theirs.peek() == Some(&item) {
+                    true
+                } else {
+                    self.nodes.free(node);
+                    false
+                }
+            });
+            self.root = link_balanced(&nodes);
+        }
+        #[cfg(feature = "stats")]
+        self.stats.record_rebalance();
+    }
+
+    /// Decompose the tree into a pointer to its root node, and a pointer to the pool its nodes
+    /// came from. The root is null if the tree is empty, and the pool is null if the tree
+    /// isn't pooled. The tree can be put back together with `from_raw_parts`,
+    /// otherwise its memory is leaked.
+    pub fn into_raw_parts(self) -> (*mut (), *mut ()) {
+        let mut tree = ManuallyDrop::new(self);
+        let root = tree
+            .root
+            .take()
+            .map_or(std::ptr::null_mut(), |root| root.as_ptr().cast());
+        // Safety: the tree is never dropped, so the allocator is only moved out once.
+        let nodes = unsafe { std::ptr::read(&tree.nodes) };
+        (root, nodes.into_raw())
+    }
+
+    /// Rebuild a tree from the pieces produced by `into_raw_parts`.
+    /// The duplicate policy isn't kept, so the tree is back to `DuplicatePolicy::Allow`.
+    ///
+    /// # Safety
+    ///
+    /// `root` and `pool` must have come from the same call to `into_raw_parts` on a tree
+    /// with the same `T`, and must not be used to rebuild another tree.
+    pub unsafe fn from_raw_parts(root: *mut (), pool: *mut ()) -> Self {
+        let root = NonNull::new(root.cast());
+        // Safety: guaranteed by the caller.
+        #[allow(unused_mut)]
+        let mut nodes = unsafe { NodeAlloc::from_raw(pool) };
+        // Safety: the root came from a valid tree.
+        #[cfg(feature = "node-ids")]
+        unsafe {
+            nodes.resume_ids(root)
+        };
+        Self {
+            root,
+            nodes,
+            duplicates: DuplicatePolicy::Allow,
+            #[cfg(feature = "stats")]
+            stats: Default::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Build a balanced tree from a vector that is already sorted.
+    fn from_sorted_vec(items: Vec<T>) -> Self {
+        Self::from_sorted_vec_in(NodeAlloc::global(), items)
+    }
+
+    /// Build a balanced tree from a vector that is already sorted, with nodes from `nodes`.
+    fn from_sorted_vec_in(nodes: NodeAlloc<T>, items: Vec<T>) -> Self {
+        let mut tree = Self::with_nodes(nodes);
+        let len = items.len();
+        tree.root = build_balanced(&mut tree.nodes, &mut items.into_iter(), len);
+        tree
+    }
+
+    /// Move every item into freshly allocated nodes, laid out in sorted order and linked into
+    /// a balanced tree. After a lot of inserts and deletes, this gets the nodes back close
+    /// together in memory, so walking the tree is kinder to the cache. Pooled trees also give
+    /// back their old chunks. With the `node-ids` feature, nodes are numbered afresh.
+    pub fn compact(&mut self) {
+        let len = self.len();
+        let mut items = Vec::with_capacity(len);
+        if let Some(root) = self.root.take() {
+            // Safety: we took the root out of the tree, so nothing else refers to it.
+            unsafe { drain_node(&mut self.nodes, root, &mut items) };
+        }
+        self.nodes = self.nodes.new_like();
+        self.root = build_balanced(&mut self.nodes, &mut items.into_iter(), len);
+        #[cfg(feature = "stats")]
+        self.stats.record_rebalance();
+    }
+
+    /// Move the items out of the tree in sorted order.
+    fn into_sorted_vec(mut self) -> Vec<T> {
+        let mut out = Vec::with_capacity(self.len());
+        if let Some(root) = self.root.take() {
+            // Safety: we took the root out of the tree, so nothing else refers to it.
+            unsafe { drain_node(&mut self.nodes, root, &mut out) };
+        }
+        out
+    }
+
+    /// Insert an item. If an equal item is already in the tree, what happens depends on the
+    /// tree's duplicate policy: with `Allow` the new item goes after it, with `Reject` the new
+    /// item is handed back, and with `Replace` it takes the old one's place, which is handed back.
+    pub fn insert(&mut self, value: T) -> Option<T>
+    where
+        T: Ord,
+    {
+        match self.duplicates {
+            DuplicatePolicy::Allow => {
+                self.attach(value);
+                None
+            }
+            DuplicatePolicy::Reject => self.try_insert(value).err().map(|err| err.value),
+            DuplicatePolicy::Replace => self.replace(value),
+        }
+    }
+
+    /// Insert an item after any equal ones.
+    fn attach(&mut self, value: T)
+    where
+        T: Ord,
+    {
+        let path_len = unsafe {
+            let node = self.nodes.alloc(value);
+            insert_node(&mut self.root, node)
+        };
+        #[cfg(feature = "stats")]
+        self.stats.record_insert(path_len);
+        #[cfg(not(feature = "stats"))]
+        let _ = path_len;
+    }
+
+    /// Insert an item, unless an equal one is already in the tree, in which case the tree is
+    /// left as it was and the new item is dropped. Returns whether the item was inserted.
+    pub fn insert_unique(&mut self, value: T) -> bool
+    where
+        T: Ord,
+    {
+        self.try_insert(value).is_ok()
+    }
+
+    /// Insert an item, unless an equal one is already in the tree, in which case the tree is
+    /// left as it was and the new item is handed back along with the existing one.
+    pub fn try_insert(&mut self, value: T) -> Result<&T, OccupiedError<'_, T>>
+    where
+        T: Ord,
+    {
+        let (node, path_len) = unsafe { search_path(self.root, &value) };
+        if let Some(node) = node {
+            #[cfg(feature = "stats")]
+            self.stats.record_lookup(path_len);
+            return Err(OccupiedError {
+                // Safety: the node belongs to this tree, which we are borrowing.
+                existing: unsafe { &node.as_ref().item },
+                value,
+            });
+        }
+        #[cfg(not(feature = "stats"))]
+        let _ = path_len;
+
+        // Safety: the new node is valid and detached, and we have unique access to the tree.
+        unsafe {
+            let node = self.nodes.alloc(value);
+            let path_len = insert_node(&mut self.root, node);
+            #[cfg(feature = "stats")]
+            self.stats.record_insert(path_len);
+            #[cfg(not(feature = "stats"))]
+            let _ = path_len;
+            Ok(&node.as_ref().item)
+        }
+    }
+
+    /// Insert an item, unless an equal one is already in the tree, in which case
+    /// the new item takes its place and the old one is handed back.
+    pub fn replace(&mut self, value: T) -> Option<T>
+    where
+        T: Ord,
+    {
+        let (node, path_len) = unsafe { search_path(self.root, &value) };
+        let Some(mut node) = node else {
+            self.attach(value);
+            return None;
+        };
+        #[cfg(feature = "stats")]
+        self.stats.record_lookup(path_len);
+        #[cfg(not(feature = "stats"))]
+        let _ = path_len;
+        // Safety: the node belongs to this tree, which we have unique access to.
+        // The items are equal, so the tree stays in order.
+        Some(std::mem::replace(unsafe { &mut node.as_mut().item }, value))
+    }
+
+    /// Find the item equal to `key`, or if there isn't one, insert the item made by `make`,
+    /// which must be equal to `key`. Either way, hand back a reference to the item in the tree.
+    pub fn get_or_insert_with<Q, F>(&mut self, key: &Q, make: F) -> &T
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+        F: FnOnce() -> T,
+    {
+        let (node, rank, path_len) =
+            unsafe { search_rank_by(self.root, |item: &T| key.cmp(item.borrow())) };
+        #[cfg(feature = "stats")]
+        self.stats.record_lookup(path_len);
+        #[cfg(not(feature = "stats"))]
+        let _ = path_len;
+        if let Some(node) = node {
+            // Safety: the node belongs to this tree, which we are borrowing.
+            return unsafe { &node.as_ref().item };
+        }
+
+        let value = make();
+        assert!(
+            value.borrow() == key,
+            "new item must be equal to the key it was made for"
+        );
+        // Safety: the new item belongs at `rank`, as nothing has changed since we searched
+        // for it, and we have unique access to the tree. The node is valid and detached.
+        unsafe {
+            let node = self.nodes.alloc(value);
+            let path_len = insert_rank_node(&mut self.root, node, rank);
+            #[cfg(feature = "stats")]
+            self.stats.record_insert(path_len);
+            #[cfg(not(feature = "stats"))]
+            let _ = path_len;
+            &node.as_ref().item
+        }
+    }
+
+    /// Insert every value of a range, e.g. `0..1000`. The new values are built into a balanced
+    /// subtree along with any items already within the range, which is then joined back in.
+    /// Values equal to items already in the tree are dealt with by the duplicate policy.
+    pub fn insert_range(&mut self, range: Range<T>)
+    where
+        T: Successor + Ord + Clone,
+    {
+        let values = successor::values_within(&range);
+        self.insert_values_within(range, values);
+    }
+
+    /// Insert values that are in sorted order and all within `range`, the way `insert_range`
+    /// does with the values it steps through.
+    fn insert_values_within<I>(&mut self, range: Range<T>, values: I)
+    where
+        T: Ord,
+        I: Iterator<Item = T>,
+    {
+        // Safety: we have unique access to the tree, and relink every node we take out of it.
+        unsafe {
+            let (before, rest) = split_node(self.root.take(), &mut |item: &T| *item < range.start);
+            let (within, after) = split_node(rest, &mut |item: &T| *item < range.end);
+
+            let mut existing = Vec::with_capacity(size_of(within));
+            collect_nodes(within, &mut existing);
+            let mut existing = existing.into_iter().peekable();
+            let mut nodes = Vec::with_capacity(existing.len());
+            for value in values {
+                // Equal items already in the tree go first, as if the new ones were inserted.
+                while let Some(node) = existing.next_if(|node| node.as_ref().item <= value) {
+                    nodes.push(node);
+                }
+                nodes.push(self.nodes.alloc(value));
+            }
+            nodes.extend(existing);
+            self.dedup_nodes(&mut nodes);
+
+            let within = link_balanced(&nodes);
+            self.root = concat_node(concat_node(before, within), after);
+        }
+    }
+
+    pub fn get<Q>(&'a self, item: &Q) -> Option<&'a T>
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        let (node, path_len) = unsafe { search_path(self.root, item) };
+        #[cfg(feature = "stats")]
+        self.stats.record_lookup(path_len);
+        #[cfg(not(feature = "stats"))]
+        let _ = path_len;
+        unsafe { node.map(|ptr| ptr.as_ref().item()) }
+    }
+
+    /// Depth of the node holding an item equal to `key`, the root being at depth 0,
+    /// or `None` if there is no such item.
+    pub fn depth_of<Q>(&self, key: &Q) -> Option<usize>
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        let (node, path_len) = unsafe { search_path(self.root, key) };
+        #[cfg(feature = "stats")]
+        self.stats.record_lookup(path_len);
+        node.map(|_| path_len - 1)
+    }
+
+    pub fn contains<Q>(&'a self, item: &Q) -> bool
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.get(item).is_some()
+    }
+
+    pub fn min(&self) -> Option<&T>
+    where
+        T: Ord,
+    {
+        if let Some(root) = self.root {
+            unsafe { find_minimum(Some(root)) }
+        } else {
+            None
+        }
+    }
+
+    pub fn max(&self) -> Option<&T>
+    where
+        T: Ord,
+    {
+        if let Some(root) = self.root {
+            unsafe { find_maximum(Some(root)) }
+        } else {
+            None
+        }
+    }
+
+    /// The largest item not greater than `key`. Only walks one path down the tree.
+    pub fn floor<Q>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut best = None;
+        let mut current = self.root;
+        while let Some(node) = current {
+            // Safety: the node belongs to this tree, which we are borrowing.
+            let node = unsafe { node.as_ref() };
+            current = if node.item.borrow() <= key {
+                best = Some(&node.item);
+                node.right
+            } else {
+                node.left
+            };
+        }
+        best
+    }
+
+    /// The smallest item not less than `key`. Only walks one path down the tree.
+    pub fn ceiling<Q>(&self, key: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut best = None;
+        let mut current = self.root;
+        while let Some(node) = current {
+            // Safety: the node belongs to this tree, which we are borrowing.
+            let node = unsafe { node.as_ref() };
+            current = if node.item.borrow() >= key {
+                best = Some(&node.item);
+                node.left
+            } else {
+                node.right
+            };
+        }
+        best
+    }
+
+    /// The largest item less than `key` and the smallest item greater than it.
+    /// Both are found by walking down the tree once, splitting in two below any item equal to `key`.
+    pub fn neighbors<Q>(&self, key: &Q) -> (Option<&T>, Option<&T>)
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (mut below, mut above) = (None, None);
+        let mut current = self.root;
+        // Safety: the nodes belong to this tree, which we are borrowing.
+        unsafe {
+            while let Some(node) = current {
+                let node = node.as_ref();
+                current = match node.item.borrow().cmp(key) {
+                    std::cmp::Ordering::Less => {
+                        below = Some(&node.item);
+                        node.right
+                    }
+                    std::cmp::Ordering::Greater => {
+                        above = Some(&node.item);
+                        node.left
+                    }
+                    std::cmp::Ordering::Equal => {
+                        // Equal items can be on either side, so keep looking on both.
+                        let mut left = node.left;
+                        while let Some(node) = left {
+                            let node = node.as_ref();
+                            left = if node.item.borrow() < key {
+                                below = Some(&node.item);
+                                node.right
+                            } else {
+                                node.left
+                            };
+                        }
+                        let mut right = node.right;
+                        while let Some(node) = right {
+                            let node = node.as_ref();
+                            right = if node.item.borrow() > key {
+                                above = Some(&node.item);
+                                node.left
+                            } else {
+                                node.right
+                            };
+                        }
+                        None
+                    }
+                };
+            }
+        }
+        (below, above)
+    }
+
+    pub fn delete<Q>(&mut self, item: &Q)
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.remove(item);
+    }
+
+    /// Remove the smallest item, handing it back.
+    fn pop_first(&mut self) -> Option<T> {
+        // Safety: the node is detached from the tree, so we can free it.
+        unsafe { pop_first_node(&mut self.root).map(|node| self.nodes.free(node)) }
+    }
+
+    /// Remove the largest item, handing it back.
+    fn pop_last(&mut self) -> Option<T> {
+        // Safety: the node is detached from the tree, so we can free it.
+        unsafe { pop_last_node(&mut self.root).map(|node| self.nodes.free(node)) }
+    }
+
+    /// Remove an item equal to `key`, handing it back if there was one.
+    /// The same as `remove`, under the name `BTreeSet` uses.
+    pub fn take<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        self.remove(key)
+    }
+
+    /// Remove an item equal to `item`, handing it back if there was one.
+    pub fn remove<Q>(&mut self, item: &Q) -> Option<T>
+    where
+        T: Borrow<Q> + Ord,
+        Q: Ord + ?Sized,
+    {
+        let (node, path_len) = unsafe { search_path(self.root, item) };
+        #[cfg(feature = "stats")]
+        self.stats.record_delete(path_len);
+        #[cfg(not(feature = "stats"))]
+        let _ = path_len;
+        node?;
+        // Safety: we just found the item in the tree.
+        Some(unsafe { remove_found_node(&mut self.nodes, &mut self.root, item) })
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter::new(self.root)
+    }
+
+    /// Call `f` on each item in sorted order.
+    /// With parent pointers, this never allocates.
+    pub fn for_each<F>(&self, mut f: F)
+    where
+        F: FnMut(&T),
+    {
+        self.fold((), |(), item| f(item));
+    }
+
+    /// Call `f` on each item in sorted order, stopping at the first error and returning it.
+    pub fn try_for_each<E, F>(&self, mut f: F) -> Result<(), E>
+    where
+        F: FnMut(&T) -> Result<(), E>,
+    {
+        self.try_fold((), |(), item| f(item))
+    }
+
+    /// Combine the items in sorted order into one value, starting from `init`.
+    pub fn fold<B, F>(&self, init: B, mut f: F) -> B
+    where
+        F: FnMut(B, &T) -> B,
+    {
+        match self.try_fold(init, |acc, item| Ok::<_, Infallible>(f(acc, item))) {
+            Ok(acc) => acc,
+        }
+    }
+
+    /// Combine the items in sorted order into one value, starting from `init`,
+    /// stopping at the first error and returning it.
+    pub fn try_fold<B, E, F>(&self, init: B, mut f: F) -> Result<B, E>
+    where
+        F: FnMut(B, &T) -> Result<B, E>,
+    {
+        let mut acc = init;
+        // Safety: the tree is borrowed for the whole walk.
+        let mut next = unsafe { Position::first(self.root) };
+        while let Some(node) = next.node() {
+            // Safety: the node belongs to this tree, which we are borrowing.
+            unsafe {
+                acc = f(acc, &node.as_ref().item)?;
+                next.move_next();
+            }
+        }
+        Ok(acc)
+    }
+
+    /// Iterate over runs of equal items in sorted order, each as an iterator of its own.
+    /// With parent pointers, this never allocates.
+    pub fn group_by_equal(&self) -> GroupByEqual<'_, T>
+    where
+        T: Ord,
+    {
+        GroupByEqual::new(self.root)
+    }
+
+    /// Iterate over the items breadth-first: level by level from the root down,
+    /// and from left to right within each level.
+    pub fn level_order(&self) -> LevelOrder<'_, T> {
+        LevelOrder::new(self.root)
+    }
+
+    /// Iterate over the items level by level from the root down, like `level_order`, but
+    /// switching direction on every level: left to right first, then right to left, and so on.
+    pub fn zigzag(&self) -> Zigzag<'_, T> {
+        Zigzag::new(self.root)
+    }
+
+    /// Iterate over the items in sorted order, along with the depth of each one's node,
+    /// the root being at depth 0.
+    pub fn iter_with_depth(&self) -> WithDepth<'_, T> {
+        WithDepth::new(self.root)
+    }
+
+    /// Iterate over the items in pre-order: each item, then its left subtree, then its right one.
+    /// If no two items are equal, inserting them into an empty tree in this order rebuilds its shape.
+    pub fn pre_order(&self) -> PreOrder<'_, T> {
+        PreOrder::new(self.root)
+    }
+
+    /// Iterate over the items in post-order: each item's left subtree, then its right one,
+    /// then the item itself. Every item comes after everything below it.
+    pub fn post_order(&self) -> PostOrder<'_, T> {
+        PostOrder::new(self.root)
+    }
+
+    /// Iterate over clones of the items in sorted order.
+    pub fn iter_cloned(&self) -> Cloned<Iter<'_, T>>
+    where
+        T: Clone,
+    {
+        self.iter().cloned()
+    }
+
+    /// Iterate over copies of the items in sorted order.
+    pub fn iter_copied(&self) -> Copied<Iter<'_, T>>
+    where
+        T: Copy,
+    {
+        self.iter().copied()
+    }
+
+    /// Iterate over each pair of adjacent items in sorted order.
+    pub fn iter_pairs(&self) -> Pairs<'_, T> {
+        Pairs::new(self.iter())
+    }
+
+    /// Iterate over the ranges of values within `bounds` that are not in the tree,
+    /// e.g. the free ids in a tree of allocated ones.
+    pub fn gaps(&self, bounds: Range<T>) -> Gaps<'_, T>
+    where
+        T: Successor + Ord + Clone,
+    {
+        Gaps::new(self.iter(), bounds)
+    }
+
+    /// Number of levels in the tree: 0 if it is empty, 1 if it only has a root, and so on.
+    /// Walks the whole tree, without recursing, so it works however lopsided the tree is.
+    pub fn height(&self) -> usize {
+        let mut height = 0;
+        let mut stack = Vec::from_iter(self.root.map(|root| (root, 1)));
+        while let Some((node, level)) = stack.pop() {
+            height = height.max(level);
+            // Safety: the node belongs to this tree, which we are borrowing.
+            let node = unsafe { node.as_ref() };
+            stack.extend(node.left.map(|left| (left, level + 1)));
+            stack.extend(node.right.map(|right| (right, level + 1)));
+        }
+        height
+    }
+
+    /// Number of items in the tree. Every node keeps count of its subtree,
+    /// so this just reads the root's count.
+    pub fn len(&self) -> usize {
+        // Safety: the root belongs to this tree, which we are borrowing.
+        unsafe { size_of(self.root) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    /// The `n`th smallest item, counting from 0, found by walking one path down the tree.
+    pub fn select(&self, n: usize) -> Option<&T> {
+        // Safety: the node belongs to this tree, which we are borrowing.
+        unsafe { select_node(self.root, n).map(|node| &node.as_ref().item) }
+    }
+
+    /// The `k`th largest item, counting from 0, so `kth_largest(0)` is the largest.
+    /// Like `select`, this walks one path down the tree.
+    pub fn kth_largest(&self, k: usize) -> Option<&T> {
+        let len = self.len();
+        if k >= len {
+            return None;
+        }
+        self.select(len - 1 - k)
+    }
+
+    /// The middle item, or the smaller of the two middle items if there are an even number.
+    pub fn median(&self) -> Option<&T> {
+        self.select(self.len().checked_sub(1)? / 2)
+    }
+
+    /// The two middle items, which are the same item if there are an odd number.
+    pub fn median_pair(&self) -> Option<(&T, &T)> {
+        let len = self.len();
+        let lower = self.select(len.checked_sub(1)? / 2)?;
+        let upper = self.select(len / 2)?;
+        Some((lower, upper))
+    }
+
+    /// Number of items less than `key`, found by walking one path down the tree.
+    pub fn rank<Q>(&self, key: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        // Safety: the tree is borrowed for the duration of the walk.
+        unsafe { count_below(self.root, &mut |item: &T| item.borrow() < key) }
+    }
+
+    /// The items at the boundaries between `q` equally sized groups of items,
+    /// using the nearest-rank method. e.g. `quantiles(4)` returns the quartiles.
+    pub fn quantiles(&self, q: usize) -> Vec<&T> {
+        let len = self.len();
+        if len == 0 {
+            return Vec::new();
+        }
+
+        (1..q)
+            .map(|k| {
+                let rank = (k * len).div_ceil(q).saturating_sub(1);
+                // Safety: rank < len, so the node exists and is borrowed along with self.
+                unsafe {
+                    &select_node(self.root, rank)
+                        .unwrap_unchecked()
+                        .as_ref()
+                        .item
+                }
+            })
+            .collect()
+    }
+
+    /// Split the items into `n` buckets of (as near as possible) equal size, in sorted order.
+    /// Empty buckets are left out, so fewer than `n` are returned when the tree is small.
+    pub fn histogram(&self, n: usize) -> Vec<Bucket<'_, T>> {
+        let len = self.len();
+        (0..n)
+            .filter_map(|i| {
+                let (start, end) = (i * len / n, (i + 1) * len / n);
+                if start == end {
+                    return None;
+                }
+                // Safety: start < end <= len, so both nodes exist and are borrowed along with self.
+                unsafe {
+                    let first = &select_node(self.root, start)?.as_ref().item;
+                    let last = &select_node(self.root, end - 1)?.as_ref().item;
+                    Some(Bucket {
+                        first,
+                        last,
+                        count: end - start,
+                    })
+                }
+            })
+            .collect()
+    }
+
+    /// The first item of each of `n` chunks of (as near as possible) equal size, after the first.
+    /// These split the tree into the same chunks as `chunks(n)`.
+    pub fn partition_points(&self, n: usize) -> Vec<&T> {
+        let len = self.len();
+        (1..n)
+            .filter_map(|i| {
+                let (start, end) = (i * len / n, (i + 1) * len / n);
+                if start == 0 || start == end {
+                    return None;
+                }
+                // Safety: start < len, so the node exists and is borrowed along with self.
+                unsafe { Some(&select_node(self.root, start)?.as_ref().item) }
+            })
+            .collect()
+    }
+
+    /// Split the items into `n` chunks of (as near as possible) equal size, in sorted order,
+    /// and iterate over each. Empty chunks are left out, so fewer than `n` are returned
+    /// when the tree is small. Each iterator starts by jumping straight to its first item.
+    pub fn chunks(&self, n: usize) -> Vec<Iter<'_, T>> {
+        let len = self.len();
+        (0..n)
+            .filter_map(|i| {
+                let (start, end) = (i * len / n, (i + 1) * len / n);
+                // Safety: end <= len, and the tree is borrowed for as long as the iterators.
+                (start < end).then(|| unsafe { Iter::from_ranks(self.root, start, end) })
+            })
+            .collect()
+    }
+
+    /// Iterate over every `step`th item in sorted order, starting with the smallest.
+    /// Jumps straight to each item by rank, so only O(height) nodes are visited per item.
+    ///
+    /// Panics if `step` is zero.
+    pub fn iter_strided(&self, step: usize) -> Strided<'_, T> {
+        Strided::new(self.root, step)
+    }
+
+    /// Iterate over the items in a uniformly random order, without collecting them first.
+    #[cfg(feature = "rand")]
+    pub fn iter_shuffled<'r, R>(&self, rng: &'r mut R) -> Shuffled<'_, 'r, T, R>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        Shuffled::new(self.root, rng)
+    }
+
+    /// Pick an item uniformly at random, by picking a rank and walking one path down to it.
+    #[cfg(feature = "rand")]
+    pub fn choose<R>(&self, rng: &mut R) -> Option<&T>
+    where
+        R: rand::Rng + ?Sized,
+    {
+        if self.is_empty() {
+            return None;
+        }
+        self.select(rng.random_range(0..self.len()))
+    }
+
+    /// Iterate over the `k` largest items, largest first.
+    /// Only visits the nodes it yields, plus one path down the tree.
+    pub fn top_k(&self, k: usize) -> TopK<'_, T> {
+        TopK::new(self.root, k)
+    }
+
+    /// Find the pair of adjacent items with the smallest difference between them.
+    pub fn min_gap<D>(&self) -> Option<(&T, &T)>
+    where
+        for<'x> &'x T: Sub<&'x T, Output = D>,
+        D: Ord,
+    {
+        self.min_gap_by(|a, b| b - a)
+    }
+
+    /// Find the pair of adjacent items with the smallest distance between them,
+    /// as measured by `distance`. Ties go to the smallest pair.
+    pub fn min_gap_by<D, F>(&self, mut distance: F) -> Option<(&T, &T)>
+    where
+        F: FnMut(&T, &T) -> D,
+        D: Ord,
+    {
+        self.iter_pairs()
+            .map(|(a, b)| (distance(a, b), (a, b)))
+            .reduce(|best, next| if next.0 < best.0 { next } else { best })
+            .map(|(_, pair)| pair)
     }
 }
@@ -0,0 +1,605 @@
+use std::{borrow::Borrow, fmt, iter::FusedIterator, marker::PhantomData, ptr::NonNull};
+
+use crate::{
+    insert_rank_node, remove_found_node_by, remove_rank_node, search_path_by, search_rank_by,
+    BinarySearchTree, Iter, Link, Node, Position,
+};
+
+/// A key and its value, as stored in a `BstMap`'s nodes. Ordered by key alone,
+/// so the set machinery can insert and walk them as if they were plain items.
+#[derive(Clone)]
+pub(crate) struct MapEntry<K, V> {
+    pub(crate) key: K,
+    pub(crate) value: V,
+}
+
+impl<K: PartialEq, V> PartialEq for MapEntry<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<K: Eq, V> Eq for MapEntry<K, V> {}
+
+impl<K: Ord, V> PartialOrd for MapEntry<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord, V> Ord for MapEntry<K, V> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// A map from keys to values, kept in a binary search tree ordered by key.
+/// Each key appears at most once.
+#[derive(Clone)]
+pub struct BstMap<K, V> {
+    pub(crate) tree: BinarySearchTree<MapEntry<K, V>>,
+}
+
+impl<K, V> Default for BstMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> BstMap<K, V> {
+    pub fn new() -> Self {
+        Self {
+            tree: BinarySearchTree::new(),
+        }
+    }
+
+    /// Create a map whose nodes come from a pool, as for `BinarySearchTree::new_pooled`.
+    pub fn new_pooled() -> Self {
+        Self {
+            tree: BinarySearchTree::new_pooled(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tree.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tree.is_empty()
+    }
+
+    /// Iterate over the keys and values, in order of key.
+    pub fn iter(&self) -> MapIter<'_, K, V> {
+        MapIter {
+            iter: self.tree.iter(),
+        }
+    }
+
+    /// Iterate over the keys, in order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { iter: self.iter() }
+    }
+
+    /// Iterate over the values, in order of key.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { iter: self.iter() }
+    }
+
+    /// Iterate over the keys and mutable references to the values, in order of key.
+    /// The keys can only be read, so the map can't be put out of order.
+    pub fn iter_mut(&mut self) -> MapIterMut<'_, K, V> {
+        // Safety: the map is mutably borrowed for as long as the iterator.
+        unsafe { MapIterMut::from_ranks(self.tree.root, 0, self.len()) }
+    }
+
+    /// Iterate over mutable references to the values, in order of key.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut {
+            iter: self.iter_mut(),
+        }
+    }
+}
+
+impl<K: Ord, V> BstMap<K, V> {
+    /// Insert a value under `key`. If the key was already in the map, its value is replaced
+    /// and the old one handed back, while the key in the map is left as it was.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, value)| value)
+    }
+
+    /// The key stored in the map that is equal to `key`, along with its value.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (node, path_len) =
+            unsafe { search_path_by(self.tree.root, |entry| key.cmp(entry.key.borrow())) };
+        #[cfg(feature = "stats")]
+        self.tree.stats.record_lookup(path_len);
+        #[cfg(not(feature = "stats"))]
+        let _ = path_len;
+        // Safety: the node belongs to the tree, which we are borrowing.
+        let entry = unsafe { &node?.as_ref().item };
+        Some((&entry.key, &entry.value))
+    }
+
+    /// A mutable reference to the value under `key`. The key itself stays out of reach,
+    /// so the map can't be put out of order.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let (node, path_len) =
+            unsafe { search_path_by(self.tree.root, |entry| key.cmp(entry.key.borrow())) };
+        #[cfg(feature = "stats")]
+        self.tree.stats.record_lookup(path_len);
+        #[cfg(not(feature = "stats"))]
+        let _ = path_len;
+        // Safety: the node belongs to the tree, which we have unique access to.
+        node.map(|mut node| unsafe { &mut node.as_mut().item.value })
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// Remove the value under `key`, handing it back if there was one.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    /// Remove the key equal to `key` and its value, handing both back if they were there.
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let mut cmp = |entry: &MapEntry<K, V>| key.cmp(entry.key.borrow());
+        let (node, path_len) = unsafe { search_path_by(self.tree.root, &mut cmp) };
+        #[cfg(feature = "stats")]
+        self.tree.stats.record_delete(path_len);
+        #[cfg(not(feature = "stats"))]
+        let _ = path_len;
+        node?;
+        // Safety: we just found the key in the tree.
+        let entry = unsafe { remove_found_node_by(&mut self.tree.nodes, &mut self.tree.root, cmp) };
+        Some((entry.key, entry.value))
+    }
+}
+
+impl<K: Ord, V> BstMap<K, V> {
+    /// The entry for `key`, for looking at or updating its value, or filling it in if there is
+    /// none, after a single search. Filling in a vacant entry walks down the tree once more to
+    /// fix up subtree sizes, but by rank, without comparing any more keys.
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let (node, rank, path_len) =
+            unsafe { search_rank_by(self.tree.root, |entry| key.cmp(&entry.key)) };
+        #[cfg(feature = "stats")]
+        self.tree.stats.record_lookup(path_len);
+        #[cfg(not(feature = "stats"))]
+        let _ = path_len;
+        match node {
+            Some(node) => Entry::Occupied(OccupiedEntry {
+                map: self,
+                node,
+                rank,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                map: self,
+                key,
+                rank,
+            }),
+        }
+    }
+}
+
+/// The place in a `BstMap` for a key, which may or may not have a value yet.
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+/// An entry whose key is in the map.
+pub struct OccupiedEntry<'a, K, V> {
+    map: &'a mut BstMap<K, V>,
+    node: NonNull<Node<MapEntry<K, V>>>,
+    rank: usize,
+}
+
+// Safety: the entry borrows the map mutably, and the node is one of the map's own.
+unsafe impl<K: Send, V: Send> Send for OccupiedEntry<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for OccupiedEntry<'_, K, V> {}
+
+/// An entry whose key isn't in the map yet.
+pub struct VacantEntry<'a, K, V> {
+    map: &'a mut BstMap<K, V>,
+    key: K,
+    /// Rank the key will have once it is inserted.
+    rank: usize,
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// The value, after inserting `default` if there wasn't one.
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    /// The value, after inserting the one made by `make` if there wasn't one.
+    pub fn or_insert_with<F: FnOnce() -> V>(self, make: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(make()),
+        }
+    }
+
+    /// The value, after inserting `V::default()` if there wasn't one.
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    /// Call `f` on the value if there is one, and hand the entry back.
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    fn entry(&self) -> &MapEntry<K, V> {
+        // Safety: the node belongs to the map, which we are borrowing.
+        unsafe { &self.node.as_ref().item }
+    }
+
+    pub fn key(&self) -> &K {
+        &self.entry().key
+    }
+
+    pub fn get(&self) -> &V {
+        &self.entry().value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        // Safety: the node belongs to the map, which we have unique access to.
+        unsafe { &mut self.node.as_mut().item.value }
+    }
+
+    /// The value, borrowed for as long as the map was.
+    pub fn into_mut(mut self) -> &'a mut V {
+        // Safety: as in `get_mut`, and the map stays borrowed for 'a.
+        unsafe { &mut self.node.as_mut().item.value }
+    }
+
+    /// Replace the value, handing back the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    /// Remove the key and its value from the map, handing back the value.
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    /// Remove the key and its value from the map, handing both back.
+    pub fn remove_entry(self) -> (K, V) {
+        let tree = &mut self.map.tree;
+        // Safety: the node is in the tree, at the rank we found it at.
+        let entry = unsafe { remove_rank_node(&mut tree.nodes, &mut tree.root, self.rank) };
+        (entry.key, entry.value)
+    }
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Take back the key, without inserting anything.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Insert a value under the key, and hand back a reference to it.
+    pub fn insert(self, value: V) -> &'a mut V {
+        let VacantEntry { map, key, rank } = self;
+        let tree = &mut map.tree;
+        // Safety: the key belongs at `rank`, as nothing has changed since we searched
+        // for it, and we have unique access to the tree.
+        unsafe {
+            let mut node = tree.nodes.alloc(MapEntry { key, value });
+            let path_len = insert_rank_node(&mut tree.root, node, rank);
+            #[cfg(feature = "stats")]
+            tree.stats.record_insert(path_len);
+            #[cfg(not(feature = "stats"))]
+            let _ = path_len;
+            &mut node.as_mut().item.value
+        }
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for BstMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// If a key comes up more than once, the last value given for it wins.
+impl<K: Ord, V> FromIterator<(K, V)> for BstMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut entries: Vec<_> = iter
+            .into_iter()
+            .map(|(key, value)| MapEntry { key, value })
+            .collect();
+        entries.sort();
+        entries.dedup_by(|later, earlier| {
+            let same = later.key == earlier.key;
+            if same {
+                std::mem::swap(&mut later.value, &mut earlier.value);
+            }
+            same
+        });
+        Self {
+            tree: BinarySearchTree::from_sorted_vec(entries),
+        }
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a BstMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = MapIter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the keys and values of a `BstMap`, in order of key.
+pub struct MapIter<'a, K, V> {
+    iter: Iter<'a, MapEntry<K, V>>,
+}
+
+impl<K, V> Clone for MapIter<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            iter: self.iter.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for MapIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|entry| (&entry.key, &entry.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for MapIter<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter
+            .next_back()
+            .map(|entry| (&entry.key, &entry.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for MapIter<'_, K, V> {}
+
+impl<K, V> FusedIterator for MapIter<'_, K, V> {}
+
+/// An iterator over the keys of a `BstMap`, in order.
+pub struct Keys<'a, K, V> {
+    iter: MapIter<'a, K, V>,
+}
+
+impl<K, V> Clone for Keys<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            iter: self.iter.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(key, _)| key)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Keys<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(key, _)| key)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Keys<'_, K, V> {}
+
+impl<K, V> FusedIterator for Keys<'_, K, V> {}
+
+/// An iterator over the values of a `BstMap`, in order of key.
+pub struct Values<'a, K, V> {
+    iter: MapIter<'a, K, V>,
+}
+
+impl<K, V> Clone for Values<'_, K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            iter: self.iter.clone(),
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Values<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<K, V> ExactSizeIterator for Values<'_, K, V> {}
+
+impl<K, V> FusedIterator for Values<'_, K, V> {}
+
+/// An iterator over the keys of a `BstMap` and mutable references to their values,
+/// in order of key. Each value is handed out once, and only keys and values, never whole
+/// nodes, are borrowed, so walking on to the next node never touches a value already
+/// handed out.
+pub struct MapIterMut<'a, K, V> {
+    front: Position<MapEntry<K, V>>,
+    back: Position<MapEntry<K, V>>,
+    /// Number of entries between `front` and `back`, inclusive.
+    len: usize,
+    _marker: PhantomData<(&'a K, &'a mut V)>,
+}
+
+// Safety: a `MapIterMut` hands out `&mut V` and only reads keys, like a `(&K, &mut V)` would.
+unsafe impl<K: Sync, V: Send> Send for MapIterMut<'_, K, V> {}
+unsafe impl<K: Sync, V: Sync> Sync for MapIterMut<'_, K, V> {}
+
+impl<K, V> MapIterMut<'_, K, V> {
+    /// Iterate over the entries with ranks from `start` up to (but not including) `end`.
+    /// Safety: root must belong to a map that is mutably borrowed for 'a, with at least `end`
+    /// entries, and no other iterator may be handing out values with ranks in that range.
+    pub(crate) unsafe fn from_ranks(root: Link<MapEntry<K, V>>, start: usize, end: usize) -> Self {
+        let (front, back) = if start < end {
+            // Safety: guaranteed by the caller.
+            unsafe {
+                (
+                    Position::at_rank(root, start),
+                    Position::at_rank(root, end - 1),
+                )
+            }
+        } else {
+            (Position::root(None), Position::root(None))
+        };
+        Self {
+            front,
+            back,
+            len: end.saturating_sub(start),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, K, V> Iterator for MapIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // Safety: there are entries left, so `front` is on a node whose value hasn't been
+        // handed out yet. The map is mutably borrowed for 'a.
+        unsafe {
+            let node = self.front.node().unwrap_unchecked().as_ptr();
+            self.front.move_next();
+            Some((&(*node).item.key, &mut (*node).item.value))
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V> DoubleEndedIterator for MapIterMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // Safety: as in `next`.
+        unsafe {
+            let node = self.back.node().unwrap_unchecked().as_ptr();
+            self.back.move_prev();
+            Some((&(*node).item.key, &mut (*node).item.value))
+        }
+    }
+}
+
+impl<K, V> ExactSizeIterator for MapIterMut<'_, K, V> {}
+
+impl<K, V> FusedIterator for MapIterMut<'_, K, V> {}
+
+/// An iterator over mutable references to the values of a `BstMap`, in order of key.
+pub struct ValuesMut<'a, K, V> {
+    iter: MapIterMut<'a, K, V>,
+}
+
+impl<'a, K, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(_, value)| value)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for ValuesMut<'_, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(_, value)| value)
+    }
+}
+
+impl<K, V> ExactSizeIterator for ValuesMut<'_, K, V> {}
+
+impl<K, V> FusedIterator for ValuesMut<'_, K, V> {}
@@ -0,0 +1,233 @@
+use std::{borrow::Borrow, fmt, iter::FusedIterator};
+
+use crate::{BstMap, MapIter};
+
+/// A sorted collection of items that can each appear any number of times. Equal items share
+/// a single node holding how many there are, so heavy duplication doesn't grow the tree.
+#[derive(Clone)]
+pub struct BstMultiSet<T> {
+    counts: BstMap<T, usize>,
+    /// Number of items, counting duplicates.
+    len: usize,
+}
+
+impl<T> Default for BstMultiSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> BstMultiSet<T> {
+    pub fn new() -> Self {
+        Self {
+            counts: BstMap::new(),
+            len: 0,
+        }
+    }
+
+    /// Number of items, counting duplicates.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Number of distinct items.
+    pub fn distinct_len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Iterate over the items in sorted order, repeating each as many times as it appears.
+    pub fn iter(&self) -> MultiIter<'_, T> {
+        MultiIter {
+            counts: self.counts.iter(),
+            front: None,
+            back: None,
+            len: self.len,
+        }
+    }
+
+    /// Iterate over the distinct items in sorted order, along with how many times each appears.
+    pub fn counts(&self) -> MapIter<'_, T, usize> {
+        self.counts.iter()
+    }
+}
+
+impl<T: Ord> BstMultiSet<T> {
+    pub fn insert(&mut self, value: T) {
+        self.insert_n(value, 1);
+    }
+
+    /// Add `n` copies of an item. Adding none does nothing.
+    pub fn insert_n(&mut self, value: T, n: usize) {
+        if n == 0 {
+            return;
+        }
+        *self.counts.entry(value).or_insert(0) += n;
+        self.len += n;
+    }
+
+    /// Number of times an item equal to `key` appears.
+    pub fn count<Q>(&self, key: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.counts.get(key).copied().unwrap_or(0)
+    }
+
+    pub fn contains<Q>(&self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.counts.contains_key(key)
+    }
+
+    /// Remove one copy of an item equal to `key`, returning whether there was one.
+    pub fn remove_one<Q>(&mut self, key: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let Some(count) = self.counts.get_mut(key) else {
+            return false;
+        };
+        if *count == 1 {
+            self.counts.remove(key);
+        } else {
+            *count -= 1;
+        }
+        self.len -= 1;
+        true
+    }
+
+    /// Remove every copy of an item equal to `key`, returning how many there were.
+    pub fn remove_all<Q>(&mut self, key: &Q) -> usize
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let count = self.counts.remove(key).unwrap_or(0);
+        self.len -= count;
+        count
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for BstMultiSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord> Extend<T> for BstMultiSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl<T: Ord> FromIterator<T> for BstMultiSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut items: Vec<T> = iter.into_iter().collect();
+        items.sort();
+        let len = items.len();
+        let mut counts: Vec<(T, usize)> = Vec::new();
+        for item in items {
+            match counts.last_mut() {
+                Some((last, count)) if *last == item => *count += 1,
+                _ => counts.push((item, 1)),
+            }
+        }
+        Self {
+            counts: counts.into_iter().collect(),
+            len,
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a BstMultiSet<T> {
+    type Item = &'a T;
+    type IntoIter = MultiIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the items of a `BstMultiSet` in sorted order,
+/// repeating each as many times as it appears.
+pub struct MultiIter<'a, T> {
+    counts: MapIter<'a, T, usize>,
+    /// The item being repeated at the front, and how many more times to yield it.
+    front: Option<(&'a T, usize)>,
+    /// The same for the back.
+    back: Option<(&'a T, usize)>,
+    /// Number of items left to yield, counting repeats.
+    len: usize,
+}
+
+impl<T> Clone for MultiIter<'_, T> {
+    fn clone(&self) -> Self {
+        Self {
+            counts: self.counts.clone(),
+            front: self.front,
+            back: self.back,
+            len: self.len,
+        }
+    }
+}
+
+impl<'a, T> Iterator for MultiIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        if let Some((item, left @ 1..)) = &mut self.front {
+            *left -= 1;
+            return Some(item);
+        }
+        if let Some((item, &count)) = self.counts.next() {
+            self.front = Some((item, count - 1));
+            return Some(item);
+        }
+        // Whatever is left is being repeated at the back.
+        let (item, left) = self.back.as_mut()?;
+        *left -= 1;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> DoubleEndedIterator for MultiIter<'_, T> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        if let Some((item, left @ 1..)) = &mut self.back {
+            *left -= 1;
+            return Some(item);
+        }
+        if let Some((item, &count)) = self.counts.next_back() {
+            self.back = Some((item, count - 1));
+            return Some(item);
+        }
+        let (item, left) = self.front.as_mut()?;
+        *left -= 1;
+        Some(item)
+    }
+}
+
+impl<T> ExactSizeIterator for MultiIter<'_, T> {}
+
+impl<T> FusedIterator for MultiIter<'_, T> {}
@@ -0,0 +1,251 @@
+use std::marker::PhantomData;
+
+use rayon::iter::{
+    plumbing::{bridge, Consumer, Producer, ProducerCallback, UnindexedConsumer},
+    IndexedParallelIterator, IntoParallelIterator, ParallelIterator,
+};
+
+use crate::{map::MapEntry, BinarySearchTree, BstMap, Iter, Link, MapIterMut};
+
+/// A parallel iterator over the items of a `BinarySearchTree`, in sorted order.
+/// Splits the items by rank, so each thread gets an even share however the tree is shaped.
+pub struct ParIter<'a, T> {
+    producer: RankProducer<'a, T>,
+}
+
+/// The items with ranks from `start` up to (but not including) `end`.
+struct RankProducer<'a, T> {
+    root: Link<T>,
+    start: usize,
+    end: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+// Safety: the producer only reads items through a shared borrow of the tree,
+// which is fine from any thread as long as the items themselves can be shared.
+unsafe impl<T: Sync> Send for RankProducer<'_, T> {}
+
+impl<'a, T: Sync> Producer for RankProducer<'a, T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        // Safety: the tree is borrowed for 'a, and has at least `end` items.
+        unsafe { Iter::from_ranks(self.root, self.start, self.end) }
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        self.split(index)
+    }
+}
+
+impl<T> RankProducer<'_, T> {
+    /// Split into the first `index` ranks and the rest.
+    fn split(self, index: usize) -> (Self, Self) {
+        let mid = self.start + index;
+        (Self { end: mid, ..self }, Self { start: mid, ..self })
+    }
+}
+
+impl<T> Clone for RankProducer<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for RankProducer<'_, T> {}
+
+impl<'a, T: Sync> ParallelIterator for ParIter<'a, T> {
+    type Item = &'a T;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<T: Sync> IndexedParallelIterator for ParIter<'_, T> {
+    fn len(&self) -> usize {
+        self.producer.end - self.producer.start
+    }
+
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        callback.callback(self.producer)
+    }
+}
+
+impl<'a, T: Sync> IntoParallelIterator for &'a BinarySearchTree<T> {
+    type Iter = ParIter<'a, T>;
+    type Item = &'a T;
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter()
+    }
+}
+
+impl<T: Sync> BinarySearchTree<T> {
+    /// Iterate over the items in parallel, with rayon.
+    pub fn par_iter(&self) -> ParIter<'_, T> {
+        ParIter {
+            producer: RankProducer {
+                root: self.root,
+                start: 0,
+                end: self.len(),
+                _marker: PhantomData,
+            },
+        }
+    }
+}
+
+/// A parallel iterator over the keys of a `BstMap` and mutable references to their values,
+/// in order of key. Like `ParIter`, it splits the entries by rank, and each thread only ever
+/// touches the values in its own share.
+pub struct ParIterMut<'a, K, V> {
+    producer: RankMutProducer<'a, K, V>,
+}
+
+/// A parallel iterator over mutable references to the values of a `BstMap`, in order of key.
+pub struct ParValuesMut<'a, K, V> {
+    iter: ParIterMut<'a, K, V>,
+}
+
+/// The entries of a mutably borrowed map with ranks in some range. Unlike `RankProducer`,
+/// this can't be copied, so no two producers ever cover the same rank.
+struct RankMutProducer<'a, K, V> {
+    ranks: RankProducer<'a, MapEntry<K, V>>,
+    _marker: PhantomData<&'a mut V>,
+}
+
+// Safety: the producer hands out `&mut V` and only reads keys, like a `(&K, &mut V)` would,
+// and the ranges of ranks given to different threads never overlap.
+unsafe impl<K: Sync, V: Send> Send for RankMutProducer<'_, K, V> {}
+
+impl<'a, K: Sync, V: Send> Producer for RankMutProducer<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = MapIterMut<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let RankProducer {
+            root, start, end, ..
+        } = self.ranks;
+        // Safety: the map is mutably borrowed for 'a and has at least `end` entries, and this
+        // producer is the only one covering these ranks.
+        unsafe { MapIterMut::from_ranks(root, start, end) }
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let (left, right) = self.ranks.split(index);
+        (
+            Self {
+                ranks: left,
+                _marker: PhantomData,
+            },
+            Self {
+                ranks: right,
+                _marker: PhantomData,
+            },
+        )
+    }
+}
+
+impl<'a, K: Sync, V: Send> ParallelIterator for ParIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<K: Sync, V: Send> IndexedParallelIterator for ParIterMut<'_, K, V> {
+    fn len(&self) -> usize {
+        self.producer.ranks.end - self.producer.ranks.start
+    }
+
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        callback.callback(self.producer)
+    }
+}
+
+impl<'a, K: Sync, V: Send> ParallelIterator for ParValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn drive_unindexed<C>(self, consumer: C) -> C::Result
+    where
+        C: UnindexedConsumer<Self::Item>,
+    {
+        self.iter.map(|(_, value)| value).drive_unindexed(consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.len())
+    }
+}
+
+impl<K: Sync, V: Send> IndexedParallelIterator for ParValuesMut<'_, K, V> {
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        self.iter.map(|(_, value)| value).drive(consumer)
+    }
+
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        self.iter.map(|(_, value)| value).with_producer(callback)
+    }
+}
+
+impl<'a, K: Sync, V: Send> IntoParallelIterator for &'a mut BstMap<K, V> {
+    type Iter = ParIterMut<'a, K, V>;
+    type Item = (&'a K, &'a mut V);
+
+    fn into_par_iter(self) -> Self::Iter {
+        self.par_iter_mut()
+    }
+}
+
+impl<K: Sync, V: Send> BstMap<K, V> {
+    /// Iterate over the keys and mutable references to the values in parallel, with rayon.
+    pub fn par_iter_mut(&mut self) -> ParIterMut<'_, K, V> {
+        ParIterMut {
+            producer: RankMutProducer {
+                ranks: RankProducer {
+                    root: self.tree.root,
+                    start: 0,
+                    end: self.len(),
+                    _marker: PhantomData,
+                },
+                _marker: PhantomData,
+            },
+        }
+    }
+
+    /// Iterate over mutable references to the values in parallel, with rayon, e.g. to
+    /// recompute every value at once.
+    pub fn par_values_mut(&mut self) -> ParValuesMut<'_, K, V> {
+        ParValuesMut {
+            iter: self.par_iter_mut(),
+        }
+    }
+}
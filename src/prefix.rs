@@ -0,0 +1,42 @@
+use crate::{count_below, BinarySearchTree, Iter};
+
+/// Keys made up of several parts, like `(tenant_id, timestamp)`, that are ordered by their
+/// first part before anything else, so that keys sharing a prefix sit next to each other.
+pub trait KeyPrefix<P: ?Sized> {
+    fn key_prefix(&self) -> &P;
+}
+
+impl<A, B> KeyPrefix<A> for (A, B) {
+    fn key_prefix(&self) -> &A {
+        &self.0
+    }
+}
+
+impl<A, B, C> KeyPrefix<A> for (A, B, C) {
+    fn key_prefix(&self) -> &A {
+        &self.0
+    }
+}
+
+impl<A, B, C, D> KeyPrefix<A> for (A, B, C, D) {
+    fn key_prefix(&self) -> &A {
+        &self.0
+    }
+}
+
+impl<T> BinarySearchTree<T> {
+    /// Iterate over the items whose keys start with `prefix`, in sorted order.
+    /// Jumps straight to either end of the run of matching items.
+    pub fn range_prefix<P>(&self, prefix: &P) -> Iter<'_, T>
+    where
+        T: KeyPrefix<P>,
+        P: Ord + ?Sized,
+    {
+        // Safety: the tree is borrowed for as long as the iterator.
+        unsafe {
+            let start = count_below(self.root, &mut |item: &T| item.key_prefix() < prefix);
+            let end = count_below(self.root, &mut |item: &T| item.key_prefix() <= prefix);
+            Iter::from_ranks(self.root, start, end)
+        }
+    }
+}
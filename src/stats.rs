@@ -0,0 +1,107 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed};
+
+use crate::BinarySearchTree;
+
+/// Counts of the operations performed on a tree, and of how deep they had to go.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub inserts: u64,
+    pub deletes: u64,
+    pub lookups: u64,
+    /// Number of times the tree was rebuilt into a balanced shape.
+    pub rebalances: u64,
+    /// Number of paths from the root that were walked, and the nodes visited on them.
+    pub paths: u64,
+    pub total_path_len: u64,
+    pub max_path_len: usize,
+}
+
+impl Stats {
+    /// Mean number of nodes visited per walked path, or 0 if none were walked.
+    pub fn average_path_len(&self) -> f64 {
+        if self.paths == 0 {
+            0.0
+        } else {
+            self.total_path_len as f64 / self.paths as f64
+        }
+    }
+}
+
+/// A tree's statistics, which can be updated through a shared reference by lookups. Each count
+/// is updated on its own, so a snapshot taken while other threads are looking things up may
+/// be mid-way through recording one of them.
+#[derive(Default)]
+pub(crate) struct Counters {
+    inserts: AtomicU64,
+    deletes: AtomicU64,
+    lookups: AtomicU64,
+    rebalances: AtomicU64,
+    paths: AtomicU64,
+    total_path_len: AtomicU64,
+    max_path_len: AtomicUsize,
+}
+
+impl Counters {
+    fn record_path(&self, len: usize) {
+        self.paths.fetch_add(1, Relaxed);
+        self.total_path_len.fetch_add(len as u64, Relaxed);
+        self.max_path_len.fetch_max(len, Relaxed);
+    }
+
+    pub(crate) fn record_insert(&self, path_len: usize) {
+        self.inserts.fetch_add(1, Relaxed);
+        self.record_path(path_len);
+    }
+
+    pub(crate) fn record_delete(&self, path_len: usize) {
+        self.deletes.fetch_add(1, Relaxed);
+        self.record_path(path_len);
+    }
+
+    pub(crate) fn record_lookup(&self, path_len: usize) {
+        self.lookups.fetch_add(1, Relaxed);
+        self.record_path(path_len);
+    }
+
+    pub(crate) fn record_rebalance(&self) {
+        self.rebalances.fetch_add(1, Relaxed);
+    }
+
+    fn get(&self) -> Stats {
+        Stats {
+            inserts: self.inserts.load(Relaxed),
+            deletes: self.deletes.load(Relaxed),
+            lookups: self.lookups.load(Relaxed),
+            rebalances: self.rebalances.load(Relaxed),
+            paths: self.paths.load(Relaxed),
+            total_path_len: self.total_path_len.load(Relaxed),
+            max_path_len: self.max_path_len.load(Relaxed),
+        }
+    }
+
+    fn reset(&self) {
+        for count in [
+            &self.inserts,
+            &self.deletes,
+            &self.lookups,
+            &self.rebalances,
+            &self.paths,
+            &self.total_path_len,
+        ] {
+            count.store(0, Relaxed);
+        }
+        self.max_path_len.store(0, Relaxed);
+    }
+}
+
+impl<T> BinarySearchTree<T> {
+    /// The operations counted since the tree was created, or since `reset_stats`.
+    pub fn stats(&self) -> Stats {
+        self.stats.get()
+    }
+
+    /// Start counting from zero again.
+    pub fn reset_stats(&self) {
+        self.stats.reset();
+    }
+}
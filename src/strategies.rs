@@ -0,0 +1,71 @@
+//! Proptest strategies for generating `BinarySearchTree`s, for property-testing code that
+//! consumes them.
+
+use std::fmt::Debug;
+
+use proptest::{
+    arbitrary::any,
+    bool::weighted,
+    collection::{vec, SizeRange},
+    sample::Index,
+    strategy::Strategy,
+};
+
+use crate::BinarySearchTree;
+
+/// What kind of trees `trees` generates.
+#[derive(Debug, Clone)]
+pub struct TreeParams {
+    /// How many items the trees hold.
+    pub size: SizeRange,
+    /// Chance of each item being inserted in sorted order after the rest have been built
+    /// into a balanced tree. At 0.0 trees are balanced; at 1.0 they are a single vine.
+    pub skew: f64,
+    /// Chance of each item after the first being a copy of an earlier one.
+    pub duplicates: f64,
+}
+
+impl Default for TreeParams {
+    fn default() -> Self {
+        Self {
+            size: (0..100).into(),
+            skew: 0.0,
+            duplicates: 0.0,
+        }
+    }
+}
+
+/// Generate trees of items from `element`, shaped according to `params`.
+/// Shrinks towards smaller trees. Panics if `skew` or `duplicates` is outside 0.0 to 1.0.
+pub fn trees<S>(element: S, params: TreeParams) -> impl Strategy<Value = BinarySearchTree<S::Value>>
+where
+    S: Strategy,
+    S::Value: Ord + Clone + Debug,
+{
+    let choices = (
+        element,
+        weighted(params.duplicates),
+        any::<Index>(),
+        weighted(params.skew),
+    );
+    vec(choices, params.size).prop_map(|choices| {
+        let mut items: Vec<(S::Value, bool)> = Vec::with_capacity(choices.len());
+        for (item, duplicate, index, skewed) in choices {
+            let item = if duplicate && !items.is_empty() {
+                items[index.index(items.len())].0.clone()
+            } else {
+                item
+            };
+            items.push((item, skewed));
+        }
+        items.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let (vine, balanced): (Vec<_>, Vec<_>) = items.into_iter().partition(|(_, skewed)| *skewed);
+        let mut tree =
+            BinarySearchTree::from_sorted_iter(balanced.into_iter().map(|(item, _)| item));
+        for (item, _) in vine {
+            tree.insert(item);
+        }
+        tree
+    })
+}
@@ -0,0 +1,44 @@
+use std::ops::Range;
+
+/// Values that have a next value, like integers, so that ranges of them can be walked one
+/// value at a time, as by `BinarySearchTree::gaps` and `BinarySearchTree::insert_range`.
+pub trait Successor: Sized {
+    /// The smallest value greater than this one, or `None` if this is the largest.
+    fn successor(&self) -> Option<Self>;
+}
+
+macro_rules! impl_successor {
+    ($($ty:ty),*) => {
+        $(
+            impl Successor for $ty {
+                fn successor(&self) -> Option<Self> {
+                    self.checked_add(1)
+                }
+            }
+        )*
+    };
+}
+
+impl_successor!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+impl Successor for char {
+    fn successor(&self) -> Option<Self> {
+        match *self {
+            // Skip over the surrogates, which aren't chars.
+            '\u{D7FF}' => Some('\u{E000}'),
+            c => char::from_u32(c as u32 + 1),
+        }
+    }
+}
+
+/// Every value of a range in order, stepping from its start to just before its end.
+pub(crate) fn values_within<T>(range: &Range<T>) -> impl Iterator<Item = T>
+where
+    T: Successor + Ord + Clone,
+{
+    let end = range.end.clone();
+    let start = Some(range.start.clone()).filter(|start| *start < end);
+    std::iter::successors(start, move |value| {
+        value.successor().filter(|next| *next < end)
+    })
+}
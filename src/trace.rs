@@ -0,0 +1,512 @@
+use std::ops::{Bound, Deref, Range, RangeBounds, SubAssign};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    successor, BinarySearchTree, Cursor, CursorMut, DrainRange, DuplicatePolicy, ExtractIf,
+    OccupiedError, Successor,
+};
+
+/// A recording of every mutating call made on a `Traced` tree, which can be replayed
+/// to rebuild the same tree from scratch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Trace<T> {
+    /// Whether the tree's nodes came from a pool.
+    pub pooled: bool,
+    pub ops: Vec<Op<T>>,
+}
+
+/// A single recorded call, with clones of its arguments.
+/// Ranges are recorded by their bounds, and other trees by their items in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Op<T> {
+    Insert(T),
+    InsertUnique(T),
+    TryInsert(T),
+    Replace(T),
+    /// A call to `get_or_insert_with`, with the item `make` made, if it was called.
+    GetOrInsertWith {
+        key: T,
+        made: Option<T>,
+    },
+    /// A call to `insert_range`, with the values it stepped through, so that replaying it
+    /// doesn't need `Successor`.
+    InsertRange {
+        range: Range<T>,
+        values: Vec<T>,
+    },
+    /// A call to `extend`, with owned or borrowed items.
+    Extend(Vec<T>),
+    Delete(T),
+    Remove(T),
+    Take(T),
+    RemoveRange((Bound<T>, Bound<T>)),
+    DrainRange((Bound<T>, Bound<T>)),
+    Drain,
+    Clear,
+    /// A call to `retain`, with what `f` returned for each item in turn.
+    Retain(Vec<bool>),
+    /// A call to `extract_if`, with what `pred` returned for each item it was asked about
+    /// before the iterator was dropped.
+    ExtractIf(Vec<bool>),
+    /// A call to `subtract`, with the items of the other tree in order.
+    Subtract(Vec<T>),
+    /// A call to `retain_common`, with the items of the other tree in order.
+    RetainCommon(Vec<T>),
+    /// A call to `splice_range`, with the items and duplicate policy of the tree they went to.
+    SpliceRange {
+        range: (Bound<T>, Bound<T>),
+        other: Vec<T>,
+        duplicates: DuplicatePolicy,
+    },
+    /// A call to `append`, with the items and duplicate policy of the tree they came from.
+    Append {
+        other: Vec<T>,
+        duplicates: DuplicatePolicy,
+    },
+    SplitOff(T),
+    Compact,
+    WithDuplicates(DuplicatePolicy),
+    /// A call to `TracedCursorMut::remove_current`, on a cursor at the given index.
+    CursorRemove(usize),
+    /// A call to `TracedCursorMut::insert_before`, on a cursor at the given index.
+    CursorInsertBefore(usize, T),
+    /// A call to `TracedCursorMut::insert_after`, on a cursor at the given index.
+    CursorInsertAfter(usize, T),
+}
+
+/// A tree that records every call that changes it into a `Trace`.
+/// It can be read like the tree it wraps, but changes have to go through its own methods,
+/// which are the same as the tree's, except that keys and ranges are of the item type.
+pub struct Traced<T> {
+    tree: BinarySearchTree<T>,
+    trace: Trace<T>,
+}
+
+impl<T> Deref for Traced<T> {
+    type Target = BinarySearchTree<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T> Default for Traced<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Traced<T> {
+    pub fn new() -> Self {
+        Self {
+            tree: BinarySearchTree::new(),
+            trace: Trace {
+                pooled: false,
+                ops: Vec::new(),
+            },
+        }
+    }
+
+    /// Create an empty traced tree whose nodes come from a pool, as with `new_pooled`.
+    pub fn new_pooled() -> Self {
+        Self {
+            tree: BinarySearchTree::new_pooled(),
+            trace: Trace {
+                pooled: true,
+                ops: Vec::new(),
+            },
+        }
+    }
+
+    /// The calls recorded so far.
+    pub fn trace(&self) -> &Trace<T> {
+        &self.trace
+    }
+
+    /// Stop tracing, handing back the tree and everything recorded.
+    pub fn into_parts(self) -> (BinarySearchTree<T>, Trace<T>) {
+        (self.tree, self.trace)
+    }
+
+    pub fn drain(&mut self) -> DrainRange<'_, T> {
+        self.trace.ops.push(Op::Drain);
+        self.tree.drain()
+    }
+
+    pub fn clear(&mut self) {
+        self.trace.ops.push(Op::Clear);
+        self.tree.clear();
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        self.trace.ops.push(Op::Retain(Vec::new()));
+        let Some(Op::Retain(kept)) = self.trace.ops.last_mut() else {
+            unreachable!()
+        };
+        self.tree.retain(|item| {
+            let keep = f(item);
+            kept.push(keep);
+            keep
+        });
+    }
+
+    /// As `BinarySearchTree::extract_if`. What `pred` returns is recorded as the iterator
+    /// advances, so the call is recorded up to wherever the iterator is dropped.
+    pub fn extract_if<'a, F>(
+        &'a mut self,
+        mut pred: F,
+    ) -> ExtractIf<'a, T, impl FnMut(&T) -> bool + 'a>
+    where
+        F: FnMut(&T) -> bool + 'a,
+    {
+        self.trace.ops.push(Op::ExtractIf(Vec::new()));
+        let Some(Op::ExtractIf(extracted)) = self.trace.ops.last_mut() else {
+            unreachable!()
+        };
+        self.tree.extract_if(move |item| {
+            let extract = pred(item);
+            extracted.push(extract);
+            extract
+        })
+    }
+
+    pub fn compact(&mut self) {
+        self.trace.ops.push(Op::Compact);
+        self.tree.compact();
+    }
+
+    /// As `BinarySearchTree::cursor_front_mut`, recording the changes made through the cursor.
+    pub fn cursor_front_mut(&mut self) -> TracedCursorMut<'_, T> {
+        TracedCursorMut {
+            cursor: self.tree.cursor_front_mut(),
+            ops: &mut self.trace.ops,
+        }
+    }
+
+    /// As `BinarySearchTree::cursor_back_mut`, recording the changes made through the cursor.
+    pub fn cursor_back_mut(&mut self) -> TracedCursorMut<'_, T> {
+        TracedCursorMut {
+            cursor: self.tree.cursor_back_mut(),
+            ops: &mut self.trace.ops,
+        }
+    }
+}
+
+impl<T: Ord + Clone> Traced<T> {
+    pub fn insert(&mut self, value: T) -> Option<T> {
+        self.trace.ops.push(Op::Insert(value.clone()));
+        self.tree.insert(value)
+    }
+
+    pub fn insert_unique(&mut self, value: T) -> bool {
+        self.trace.ops.push(Op::InsertUnique(value.clone()));
+        self.tree.insert_unique(value)
+    }
+
+    pub fn try_insert(&mut self, value: T) -> Result<&T, OccupiedError<'_, T>> {
+        self.trace.ops.push(Op::TryInsert(value.clone()));
+        self.tree.try_insert(value)
+    }
+
+    pub fn replace(&mut self, value: T) -> Option<T> {
+        self.trace.ops.push(Op::Replace(value.clone()));
+        self.tree.replace(value)
+    }
+
+    pub fn get_or_insert_with<F>(&mut self, key: &T, make: F) -> &T
+    where
+        F: FnOnce() -> T,
+    {
+        self.trace.ops.push(Op::GetOrInsertWith {
+            key: key.clone(),
+            made: None,
+        });
+        let Some(Op::GetOrInsertWith { made, .. }) = self.trace.ops.last_mut() else {
+            unreachable!()
+        };
+        self.tree.get_or_insert_with(key, || {
+            let value = make();
+            *made = Some(value.clone());
+            value
+        })
+    }
+
+    pub fn insert_range(&mut self, range: Range<T>)
+    where
+        T: Successor,
+    {
+        self.trace.ops.push(Op::InsertRange {
+            range: range.clone(),
+            values: successor::values_within(&range).collect(),
+        });
+        self.tree.insert_range(range);
+    }
+
+    pub fn delete(&mut self, item: &T) {
+        self.trace.ops.push(Op::Delete(item.clone()));
+        self.tree.delete(item);
+    }
+
+    pub fn remove(&mut self, item: &T) -> Option<T> {
+        self.trace.ops.push(Op::Remove(item.clone()));
+        self.tree.remove(item)
+    }
+
+    pub fn take(&mut self, key: &T) -> Option<T> {
+        self.trace.ops.push(Op::Take(key.clone()));
+        self.tree.take(key)
+    }
+
+    pub fn remove_range<R: RangeBounds<T>>(&mut self, range: R) -> usize {
+        let range = cloned_bounds(&range);
+        self.trace.ops.push(Op::RemoveRange(range.clone()));
+        self.tree.remove_range(range)
+    }
+
+    pub fn drain_range<R: RangeBounds<T>>(&mut self, range: R) -> DrainRange<'_, T> {
+        let range = cloned_bounds(&range);
+        self.trace.ops.push(Op::DrainRange(range.clone()));
+        self.tree.drain_range(range)
+    }
+
+    pub fn subtract(&mut self, other: &BinarySearchTree<T>) {
+        self.trace
+            .ops
+            .push(Op::Subtract(other.iter_cloned().collect()));
+        self.tree.subtract(other);
+    }
+
+    pub fn retain_common(&mut self, other: &BinarySearchTree<T>) {
+        self.trace
+            .ops
+            .push(Op::RetainCommon(other.iter_cloned().collect()));
+        self.tree.retain_common(other);
+    }
+
+    pub fn splice_range<R: RangeBounds<T>>(
+        &mut self,
+        range: R,
+        other: &mut BinarySearchTree<T>,
+    ) -> usize {
+        let range = cloned_bounds(&range);
+        self.trace.ops.push(Op::SpliceRange {
+            range: range.clone(),
+            other: other.iter_cloned().collect(),
+            duplicates: other.duplicate_policy(),
+        });
+        self.tree.splice_range(range, other)
+    }
+
+    pub fn append(&mut self, other: &mut BinarySearchTree<T>) {
+        self.trace.ops.push(Op::Append {
+            other: other.iter_cloned().collect(),
+            duplicates: other.duplicate_policy(),
+        });
+        self.tree.append(other);
+    }
+
+    pub fn split_off(&mut self, key: &T) -> BinarySearchTree<T> {
+        self.trace.ops.push(Op::SplitOff(key.clone()));
+        self.tree.split_off(key)
+    }
+
+    pub fn with_duplicates(mut self, policy: DuplicatePolicy) -> Self {
+        self.trace.ops.push(Op::WithDuplicates(policy));
+        self.tree = self.tree.with_duplicates(policy);
+        self
+    }
+
+    /// As `BinarySearchTree::cursor_at_mut`, recording the changes made through the cursor.
+    pub fn cursor_at_mut(&mut self, key: &T) -> TracedCursorMut<'_, T> {
+        TracedCursorMut {
+            cursor: self.tree.cursor_at_mut(key),
+            ops: &mut self.trace.ops,
+        }
+    }
+}
+
+impl<T: Ord + Clone> Extend<T> for Traced<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let items: Vec<T> = iter.into_iter().collect();
+        self.trace.ops.push(Op::Extend(items.clone()));
+        self.tree.extend(items);
+    }
+}
+
+impl<'a, T: Ord + Clone + 'a> Extend<&'a T> for Traced<T> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().cloned());
+    }
+}
+
+impl<T: Ord + Clone> SubAssign<&BinarySearchTree<T>> for Traced<T> {
+    fn sub_assign(&mut self, other: &BinarySearchTree<T>) {
+        self.subtract(other);
+    }
+}
+
+/// A `CursorMut` over a `Traced` tree, which records the changes made through it.
+pub struct TracedCursorMut<'a, T> {
+    cursor: CursorMut<'a, T>,
+    ops: &'a mut Vec<Op<T>>,
+}
+
+impl<T> TracedCursorMut<'_, T> {
+    pub fn current(&self) -> Option<&T> {
+        self.cursor.current()
+    }
+
+    pub fn move_next(&mut self) {
+        self.cursor.move_next();
+    }
+
+    pub fn move_prev(&mut self) {
+        self.cursor.move_prev();
+    }
+
+    pub fn peek_next(&self) -> Option<&T> {
+        self.cursor.peek_next()
+    }
+
+    pub fn peek_prev(&self) -> Option<&T> {
+        self.cursor.peek_prev()
+    }
+
+    pub fn as_cursor(&self) -> Cursor<'_, T> {
+        self.cursor.as_cursor()
+    }
+
+    pub fn remove_current(&mut self) -> Option<T> {
+        self.ops.push(Op::CursorRemove(self.cursor.index()));
+        self.cursor.remove_current()
+    }
+}
+
+impl<T: Ord + Clone> TracedCursorMut<'_, T> {
+    pub fn insert_before(&mut self, value: T) -> Result<(), T> {
+        self.ops
+            .push(Op::CursorInsertBefore(self.cursor.index(), value.clone()));
+        self.cursor.insert_before(value)
+    }
+
+    pub fn insert_after(&mut self, value: T) -> Result<(), T> {
+        self.ops
+            .push(Op::CursorInsertAfter(self.cursor.index(), value.clone()));
+        self.cursor.insert_after(value)
+    }
+}
+
+/// The bounds of a range, cloned so they can be recorded.
+fn cloned_bounds<T: Clone, R: RangeBounds<T>>(range: &R) -> (Bound<T>, Bound<T>) {
+    (range.start_bound().cloned(), range.end_bound().cloned())
+}
+
+impl<T: Ord + Clone> BinarySearchTree<T> {
+    /// Rebuild a tree by making each recorded call of a trace in turn, on a fresh tree
+    /// with the same kind of allocator. The calls made to the tree are the same as the
+    /// original ones, so any bug they ran into is run into again.
+    pub fn replay(trace: &Trace<T>) -> Self {
+        let mut tree = if trace.pooled {
+            Self::new_pooled()
+        } else {
+            Self::new()
+        };
+        for op in &trace.ops {
+            match op {
+                Op::Insert(value) => {
+                    tree.insert(value.clone());
+                }
+                Op::InsertUnique(value) => {
+                    tree.insert_unique(value.clone());
+                }
+                Op::TryInsert(value) => {
+                    let _ = tree.try_insert(value.clone());
+                }
+                Op::Replace(value) => {
+                    tree.replace(value.clone());
+                }
+                Op::GetOrInsertWith { key, made } => {
+                    tree.get_or_insert_with(key, || {
+                        made.clone()
+                            .expect("the traced call found the key, so didn't make an item")
+                    });
+                }
+                Op::InsertRange { range, values } => {
+                    tree.insert_values_within(range.clone(), values.iter().cloned())
+                }
+                Op::Extend(items) => tree.extend(items.iter().cloned()),
+                Op::Delete(item) => tree.delete(item),
+                Op::Remove(item) => {
+                    tree.remove(item);
+                }
+                Op::Take(key) => {
+                    tree.take(key);
+                }
+                Op::RemoveRange(range) => {
+                    tree.remove_range(range.clone());
+                }
+                Op::DrainRange(range) => {
+                    tree.drain_range(range.clone());
+                }
+                Op::Drain => {
+                    tree.drain();
+                }
+                Op::Clear => tree.clear(),
+                Op::Retain(kept) => {
+                    let mut kept = kept.iter().copied();
+                    tree.retain(|_| kept.next().unwrap_or(true));
+                }
+                Op::ExtractIf(extracted) => {
+                    // The iterator advanced once per item extracted, and once more if it
+                    // went on to run out of items, having been told not to extract the last.
+                    let calls = extracted.iter().filter(|&&extract| extract).count()
+                        + usize::from(extracted.last() == Some(&false));
+                    let mut extracted = extracted.iter().copied();
+                    tree.extract_if(|_| extracted.next().unwrap_or(false))
+                        .take(calls)
+                        .for_each(drop);
+                }
+                Op::Subtract(items) => tree.subtract(&Self::from_sorted_vec(items.clone())),
+                Op::RetainCommon(items) => {
+                    tree.retain_common(&Self::from_sorted_vec(items.clone()))
+                }
+                Op::SpliceRange {
+                    range,
+                    other,
+                    duplicates,
+                } => {
+                    let mut other =
+                        Self::from_sorted_vec(other.clone()).with_duplicates(*duplicates);
+                    tree.splice_range(range.clone(), &mut other);
+                }
+                Op::Append { other, duplicates } => {
+                    let mut other =
+                        Self::from_sorted_vec(other.clone()).with_duplicates(*duplicates);
+                    tree.append(&mut other);
+                }
+                Op::SplitOff(key) => {
+                    tree.split_off(key);
+                }
+                Op::Compact => tree.compact(),
+                Op::WithDuplicates(policy) => tree = tree.with_duplicates(*policy),
+                Op::CursorRemove(index) => {
+                    CursorMut::new(&mut tree, *index).remove_current();
+                }
+                Op::CursorInsertBefore(index, value) => {
+                    let _ = CursorMut::new(&mut tree, *index).insert_before(value.clone());
+                }
+                Op::CursorInsertAfter(index, value) => {
+                    let _ = CursorMut::new(&mut tree, *index).insert_after(value.clone());
+                }
+            }
+        }
+        tree
+    }
+}
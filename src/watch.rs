@@ -0,0 +1,180 @@
+use std::{
+    borrow::Borrow,
+    ops::{Bound, Deref, RangeBounds},
+    sync::mpsc::{self, Receiver, Sender},
+};
+
+use crate::{BinarySearchTree, DuplicatePolicy};
+
+/// An item entering or leaving a watched range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change<T> {
+    Entered(T),
+    Left(T),
+}
+
+impl<T: Clone> Change<&T> {
+    /// Clone the item that changed.
+    pub fn cloned(self) -> Change<T> {
+        match self {
+            Change::Entered(item) => Change::Entered(item.clone()),
+            Change::Left(item) => Change::Left(item.clone()),
+        }
+    }
+}
+
+/// Identifies a watch registered with `Watched::watch` or `Watched::watch_channel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchId(usize);
+
+type Callback<T> = Box<dyn FnMut(Change<&T>)>;
+
+enum Notify<T> {
+    Callback(Callback<T>),
+    Channel(Sender<Change<T>>),
+}
+
+struct Watcher<T> {
+    range: (Bound<T>, Bound<T>),
+    notify: Notify<T>,
+}
+
+/// A tree that tells watchers whenever an item enters or leaves a range they care about.
+/// It can be read like the tree it wraps, but changes have to go through its own methods.
+pub struct Watched<T> {
+    tree: BinarySearchTree<T>,
+    /// Registered watchers, indexed by `WatchId`. Removed ones leave a `None` behind.
+    watchers: Vec<Option<Watcher<T>>>,
+}
+
+impl<T> Deref for Watched<T> {
+    type Target = BinarySearchTree<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tree
+    }
+}
+
+impl<T> Default for Watched<T> {
+    fn default() -> Self {
+        Self::new(BinarySearchTree::new())
+    }
+}
+
+impl<T> Watched<T> {
+    /// Start watching a tree. Its current items don't cause any notifications.
+    pub fn new(tree: BinarySearchTree<T>) -> Self {
+        Self {
+            tree,
+            watchers: Vec::new(),
+        }
+    }
+
+    /// Drop every watcher, handing back the tree.
+    pub fn into_inner(self) -> BinarySearchTree<T> {
+        self.tree
+    }
+
+    /// Stop notifying a watcher. Returns false if it was already removed.
+    pub fn unwatch(&mut self, id: WatchId) -> bool {
+        self.watchers.get_mut(id.0).and_then(Option::take).is_some()
+    }
+
+    fn add_watcher(&mut self, watcher: Watcher<T>) -> WatchId {
+        self.watchers.push(Some(watcher));
+        WatchId(self.watchers.len() - 1)
+    }
+}
+
+impl<T: Ord + Clone> Watched<T> {
+    /// Call `callback` with every item that enters or leaves `range` from now on.
+    pub fn watch<R, F>(&mut self, range: R, callback: F) -> WatchId
+    where
+        R: RangeBounds<T>,
+        F: FnMut(Change<&T>) + 'static,
+    {
+        self.add_watcher(Watcher {
+            range: (range.start_bound().cloned(), range.end_bound().cloned()),
+            notify: Notify::Callback(Box::new(callback)),
+        })
+    }
+
+    /// Send a clone of every item that enters or leaves `range` from now on down a channel.
+    /// The watch is removed once the receiver is dropped.
+    pub fn watch_channel<R>(&mut self, range: R) -> (WatchId, Receiver<Change<T>>)
+    where
+        R: RangeBounds<T>,
+    {
+        let (sender, receiver) = mpsc::channel();
+        let id = self.add_watcher(Watcher {
+            range: (range.start_bound().cloned(), range.end_bound().cloned()),
+            notify: Notify::Channel(sender),
+        });
+        (id, receiver)
+    }
+
+    /// Insert an item, notifying watchers of it, and of any item it takes the place of under
+    /// the tree's duplicate policy. An item the policy rejects isn't notified at all.
+    pub fn insert(&mut self, value: T) -> Option<T> {
+        if self.tree.duplicate_policy() == DuplicatePolicy::Reject && self.tree.contains(&value) {
+            return Some(value);
+        }
+        notify(&mut self.watchers, Change::Entered(&value));
+        let replaced = self.tree.insert(value);
+        if let Some(replaced) = &replaced {
+            notify(&mut self.watchers, Change::Left(replaced));
+        }
+        replaced
+    }
+
+    /// Delete an item equal to `item`, notifying watchers of the item that was removed.
+    pub fn delete(&mut self, item: &T) {
+        self.remove(item);
+    }
+
+    /// Remove an item equal to `key`, notifying watchers of it, and hand it back if there was one.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let removed = self.tree.remove(key)?;
+        notify(&mut self.watchers, Change::Left(&removed));
+        Some(removed)
+    }
+
+    /// The same as `remove`, under the name `BTreeSet` uses.
+    pub fn take<Q>(&mut self, key: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.remove(key)
+    }
+
+    /// Drop every item, notifying watchers of each one in sorted order.
+    pub fn clear(&mut self) {
+        for item in self.tree.drain() {
+            notify(&mut self.watchers, Change::Left(&item));
+        }
+    }
+}
+
+/// Tell the watchers whose range the changed item is in about it.
+fn notify<T: Ord + Clone>(watchers: &mut [Option<Watcher<T>>], change: Change<&T>) {
+    let (Change::Entered(item) | Change::Left(item)) = change;
+    for slot in watchers {
+        let Some(watcher) = slot else { continue };
+        if !watcher.range.contains(item) {
+            continue;
+        }
+        match &mut watcher.notify {
+            Notify::Callback(callback) => callback(change),
+            Notify::Channel(sender) => {
+                if sender.send(change.cloned()).is_err() {
+                    *slot = None;
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,10 @@
-use binarysearchtree::BinarySearchTree;
+use binarysearchtree::{BinarySearchTree, BstMap, BstMultiSet, Entry, IntervalTree, Successor};
+use std::{
+    collections::{BTreeSet, BinaryHeap},
+    iter::FusedIterator,
+    ops::Bound,
+    thread,
+};
 
 #[test]
 fn can_make_one() {
@@ -60,6 +66,42 @@ fn can_find_min_and_max() {
     assert_eq!(max, 44);
 }
 
+#[test]
+fn can_find_floor_and_ceiling() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.floor(&3), None);
+    assert_eq!(tree.ceiling(&3), None);
+
+    for value in [50, 30, 70, 20, 40, 60, 80] {
+        tree.insert(value);
+    }
+    assert_eq!(tree.floor(&45), Some(&40));
+    assert_eq!(tree.floor(&40), Some(&40));
+    assert_eq!(tree.floor(&19), None);
+    assert_eq!(tree.ceiling(&45), Some(&50));
+    assert_eq!(tree.ceiling(&80), Some(&80));
+    assert_eq!(tree.ceiling(&81), None);
+}
+
+#[test]
+fn can_find_neighbors() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.neighbors(&3), (None, None));
+
+    for value in [50, 30, 70, 20, 40, 60, 80, 50, 50] {
+        tree.insert(value);
+    }
+    assert_eq!(tree.neighbors(&45), (Some(&40), Some(&50)));
+    assert_eq!(tree.neighbors(&50), (Some(&40), Some(&60)));
+    assert_eq!(tree.neighbors(&20), (None, Some(&30)));
+    assert_eq!(tree.neighbors(&80), (Some(&70), None));
+    assert_eq!(tree.neighbors(&90), (Some(&80), None));
+
+    // Built balanced, equal items sit on both sides of the root.
+    let tree = BinarySearchTree::from_sorted_iter([1, 2, 2, 2, 2, 2, 3]);
+    assert_eq!(tree.neighbors(&2), (Some(&1), Some(&3)));
+}
+
 #[test]
 fn can_delete() {
     let mut tree = BinarySearchTree::new();
@@ -109,3 +151,3041 @@ fn covariance() {
     assert!(tree.contains("Hi!"));
     assert!(!tree.contains("Hello"));
 }
+
+#[test]
+fn can_extend_from_refs() {
+    let mut tree: BinarySearchTree<i32> = BinarySearchTree::new();
+    let values = [3, 44, 5];
+    tree.extend(values.iter());
+
+    assert!(tree.contains(&3));
+    assert!(tree.contains(&44));
+    assert!(tree.contains(&5));
+
+    let mut strings: BinarySearchTree<String> = BinarySearchTree::new();
+    let words = vec![String::from("Hello"), String::from("World")];
+    strings.extend(&words);
+
+    assert!(strings.contains("Hello"));
+    assert!(strings.contains("World"));
+}
+
+#[test]
+fn can_extend_from_copied_refs_of_another_collection() {
+    let mut tree = BinarySearchTree::from([10, 20]);
+    let other = BinarySearchTree::from([15, 25]);
+    tree.extend(other.iter());
+    tree.extend([5, 30][..].iter());
+
+    assert_eq!(
+        tree.iter_copied().collect::<Vec<_>>(),
+        vec![5, 10, 15, 20, 25, 30]
+    );
+    assert_eq!(other.len(), 2);
+}
+
+#[test]
+fn can_extend_from_values() {
+    let mut tree = BinarySearchTree::new();
+    tree.extend(vec![3, 44, 5]);
+    tree.extend((0..3).map(|i| i * 10));
+    assert_eq!(
+        tree.iter_copied().collect::<Vec<_>>(),
+        vec![0, 3, 5, 10, 20, 44]
+    );
+
+    let mut strings = BinarySearchTree::new();
+    strings.extend([String::from("World"), String::from("Hello")]);
+    assert_eq!(
+        strings.iter_cloned().collect::<Vec<_>>(),
+        vec!["Hello", "World"]
+    );
+}
+
+#[test]
+fn extending_merges_large_batches() {
+    // Inserting in order leaves a degenerate tree, which extending one item at a time
+    // would take quadratic time to add to.
+    let mut tree = BinarySearchTree::new();
+    for value in 0..5 {
+        tree.insert(value);
+    }
+    tree.extend(5..200_000);
+    assert_eq!(tree.len(), 200_000);
+    assert!(tree.height() <= 20);
+    tree.extend((0..200_000).rev().step_by(2));
+    assert_eq!(tree.len(), 300_000);
+    tree.check_invariants();
+    assert_eq!(
+        tree.iter().take(4).collect::<Vec<_>>(),
+        vec![&0, &1, &1, &2]
+    );
+}
+
+#[test]
+fn extending_follows_the_duplicate_policy() {
+    use binarysearchtree::DuplicatePolicy;
+    use std::cmp::Ordering;
+
+    // Ordered by key alone, so we can tell which of two equal items is in the tree.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Tagged(i32, char);
+    impl PartialOrd for Tagged {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    impl Ord for Tagged {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    for (policy, expected) in [
+        (DuplicatePolicy::Allow, "azbcxy"),
+        (DuplicatePolicy::Reject, "abc"),
+        (DuplicatePolicy::Replace, "zby"),
+    ] {
+        let mut tree = BinarySearchTree::new().with_duplicates(policy);
+        tree.insert(Tagged(1, 'a'));
+        tree.insert(Tagged(3, 'c'));
+        tree.extend([
+            Tagged(3, 'x'),
+            Tagged(2, 'b'),
+            Tagged(3, 'y'),
+            Tagged(1, 'z'),
+        ]);
+        assert_eq!(tree.iter().map(|item| item.1).collect::<String>(), expected);
+        tree.check_invariants();
+    }
+}
+
+#[test]
+fn collecting_builds_a_balanced_tree() {
+    let tree = (0..1023).rev().collect::<BinarySearchTree<_>>();
+    tree.check_invariants();
+    assert_eq!(tree.len(), 1023);
+    assert_eq!(
+        tree.iter_with_depth().map(|(depth, _)| depth).max(),
+        Some(9)
+    );
+    assert!(tree.iter_copied().eq(0..1023));
+
+    let empty = std::iter::empty::<i32>().collect::<BinarySearchTree<_>>();
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn can_clone_trees() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for value in [5, 2, 8, 1, 9, 3, 7] {
+            tree.insert(value.to_string());
+        }
+        let copy = tree.clone();
+        copy.check_invariants();
+        assert_eq!(copy.to_dot(), tree.to_dot());
+        tree.insert("4".to_string());
+        assert_eq!(copy.len(), 7);
+
+        let mut smaller = BinarySearchTree::from(["x".to_string()]);
+        smaller.clone_from(&tree);
+        smaller.check_invariants();
+        assert_eq!(smaller.to_dot(), tree.to_dot());
+
+        let mut larger = tree.clone();
+        larger.extend(["10".to_string(), "11".to_string()]);
+        larger.clone_from(&copy);
+        larger.check_invariants();
+        assert_eq!(larger.to_dot(), copy.to_dot());
+
+        larger.clone_from(&BinarySearchTree::new());
+        assert!(larger.is_empty());
+    }
+}
+
+#[test]
+fn trees_with_the_same_items_are_equal() {
+    let mut chain = BinarySearchTree::new();
+    for value in [1, 2, 3, 3] {
+        chain.insert(value);
+    }
+    let balanced = BinarySearchTree::from_sorted_iter([1, 2, 3, 3]);
+    assert_eq!(chain, balanced);
+    assert_ne!(chain, BinarySearchTree::from([1, 2, 3]));
+    assert_ne!(chain, BinarySearchTree::from([1, 2, 3, 4]));
+    assert_eq!(
+        BinarySearchTree::<i32>::new(),
+        BinarySearchTree::new_pooled()
+    );
+}
+
+#[test]
+fn trees_are_ordered_by_their_items() {
+    let a = BinarySearchTree::from([1, 2, 3]);
+    let b = BinarySearchTree::from([1, 3]);
+    let c = BinarySearchTree::from([1, 2]);
+    assert!(a < b);
+    assert!(c < a);
+    assert!(BinarySearchTree::new() < c);
+    assert_eq!(a.partial_cmp(&a.clone()), Some(std::cmp::Ordering::Equal));
+
+    let mut trees = vec![b.clone(), a.clone(), c.clone()];
+    trees.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    assert_eq!(trees, vec![c, a, b]);
+}
+
+#[test]
+// With the `stats` feature, trees count lookups in a `Cell`, which doesn't affect their hashes.
+#[allow(clippy::mutable_key_type)]
+fn equal_trees_hash_the_same() {
+    use std::collections::HashMap;
+
+    let mut chain = BinarySearchTree::new();
+    for value in [1, 2, 3] {
+        chain.insert(value);
+    }
+    let mut counts = HashMap::new();
+    *counts.entry(chain).or_insert(0) += 1;
+    *counts
+        .entry(BinarySearchTree::from_sorted_iter([1, 2, 3]))
+        .or_insert(0) += 1;
+    *counts.entry(BinarySearchTree::from([1, 2])).or_insert(0) += 1;
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts[&BinarySearchTree::from([3, 2, 1])], 2);
+}
+
+#[test]
+fn converts_from_vecs_arrays_and_slices() {
+    let tree = BinarySearchTree::from(vec![5, 1, 44, 3, 3]);
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 3, 3, 5, 44]);
+
+    let tree = BinarySearchTree::from(["b", "c", "a"]);
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec!["a", "b", "c"]);
+
+    let words = [String::from("World"), String::from("Hello")];
+    let tree = BinarySearchTree::from(&words[..]);
+    assert_eq!(
+        tree.iter_cloned().collect::<Vec<_>>(),
+        vec!["Hello", "World"]
+    );
+}
+
+#[test]
+fn converts_to_and_from_binary_heap() {
+    let heap = BinaryHeap::from(vec![5, 1, 44, 3, 3]);
+    let tree = BinarySearchTree::from(heap);
+
+    assert_eq!(tree.min(), Some(&1));
+    assert_eq!(tree.max(), Some(&44));
+    assert!(tree.contains(&3));
+    assert!(tree.contains(&5));
+
+    let heap = BinaryHeap::from(tree);
+    assert_eq!(heap.into_sorted_vec(), vec![1, 3, 3, 5, 44]);
+}
+
+#[test]
+fn converts_to_and_from_btree_set_and_vec() {
+    let set = BTreeSet::from([5, 1, 44, 3]);
+    let mut tree = BinarySearchTree::from(set);
+    assert_eq!(tree.len(), 4);
+    assert_eq!(tree.height(), 3);
+
+    tree.insert(3);
+    assert_eq!(Vec::from(tree.clone()), vec![1, 3, 3, 5, 44]);
+    assert_eq!(
+        BTreeSet::from(tree).into_iter().collect::<Vec<_>>(),
+        vec![1, 3, 5, 44]
+    );
+}
+
+#[test]
+fn can_iterate_clones_and_copies() {
+    let mut tree = BinarySearchTree::new();
+    tree.insert(3);
+    tree.insert(44);
+    tree.insert(5);
+    tree.insert(1);
+
+    let copies: Vec<i32> = tree.iter_copied().collect();
+    assert_eq!(copies, vec![1, 3, 5, 44]);
+
+    let mut strings = BinarySearchTree::new();
+    strings.insert(String::from("World"));
+    strings.insert(String::from("Hello"));
+
+    let clones: Vec<String> = strings.iter_cloned().collect();
+    assert_eq!(clones, vec!["Hello", "World"]);
+}
+
+#[test]
+fn iterating_after_delete_follows_parent_pointers() {
+    // 30 has only a left child, 20, whose children must point up to 20's new parent.
+    // 60 has two children, and its successor 65 is the left child of 70.
+    let mut tree = BinarySearchTree::new();
+    for i in [50, 30, 20, 10, 25, 60, 55, 70, 65, 80] {
+        tree.insert(i);
+    }
+
+    tree.delete(&30);
+    let items: Vec<i32> = tree.iter_copied().collect();
+    assert_eq!(items, vec![10, 20, 25, 50, 55, 60, 65, 70, 80]);
+
+    tree.delete(&60);
+    let items: Vec<i32> = tree.iter_copied().collect();
+    assert_eq!(items, vec![10, 20, 25, 50, 55, 65, 70, 80]);
+}
+
+#[test]
+fn delete_keeps_remaining_items() {
+    let mut tree = BinarySearchTree::new();
+    for i in [50, 30, 70, 20, 40, 60, 80, 35, 45, 65] {
+        tree.insert(i);
+    }
+
+    tree.delete(&20);
+    tree.delete(&30);
+    tree.delete(&50);
+    tree.delete(&100);
+
+    let remaining = BinaryHeap::from(tree).into_sorted_vec();
+    assert_eq!(remaining, vec![35, 40, 45, 60, 65, 70, 80]);
+}
+
+#[test]
+fn can_iterate_by_reference_in_a_for_loop() {
+    let tree = BinarySearchTree::from([3, 44, 5, 1]);
+    let mut seen = Vec::new();
+    for item in &tree {
+        seen.push(*item);
+    }
+    assert_eq!(seen, vec![1, 3, 5, 44]);
+    assert_eq!(tree.len(), 4);
+}
+
+#[test]
+fn can_iterate_adjacent_pairs() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.iter_pairs().next(), None);
+
+    tree.insert(3);
+    assert_eq!(tree.iter_pairs().next(), None);
+
+    tree.insert(44);
+    tree.insert(5);
+
+    let pairs: Vec<(&i32, &i32)> = tree.iter_pairs().collect();
+    assert_eq!(pairs, vec![(&3, &5), (&5, &44)]);
+}
+
+#[test]
+fn can_find_gaps() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.gaps(0..10).collect::<Vec<_>>(), vec![0..10]);
+
+    for i in [1u32, 2, 5, 7, 7, 12] {
+        tree.insert(i);
+    }
+
+    let gaps: Vec<_> = tree.gaps(0..10).collect();
+    assert_eq!(gaps, vec![0..1, 3..5, 6..7, 8..10]);
+
+    let gaps: Vec<_> = tree.gaps(2..8).collect();
+    assert_eq!(gaps, vec![3..5, 6..7]);
+
+    let gaps: Vec<_> = tree.gaps(12..13).collect();
+    assert!(gaps.is_empty());
+
+    let mut full = BinarySearchTree::new();
+    full.insert(u8::MAX);
+    assert_eq!(full.gaps(250..u8::MAX).collect::<Vec<_>>(), vec![250..255]);
+}
+
+#[test]
+fn can_find_min_gap() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.min_gap(), None);
+
+    tree.insert(10u32);
+    assert_eq!(tree.min_gap(), None);
+
+    for i in [1, 40, 13, 30, 33] {
+        tree.insert(i);
+    }
+    assert_eq!(tree.min_gap(), Some((&10, &13)));
+
+    let mut words = BinarySearchTree::new();
+    for word in ["a", "abcd", "abcdefg", "ab"] {
+        words.insert(word);
+    }
+    let closest = words.min_gap_by(|a, b| b.len().abs_diff(a.len()));
+    assert_eq!(closest, Some((&"a", &"ab")));
+}
+
+#[test]
+fn can_take_top_k() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.top_k(3).next(), None);
+
+    for i in [50, 30, 70, 20, 40, 60, 80] {
+        tree.insert(i);
+    }
+
+    let top: Vec<i32> = tree.top_k(3).copied().collect();
+    assert_eq!(top, vec![80, 70, 60]);
+
+    let all: Vec<i32> = tree.top_k(100).copied().collect();
+    assert_eq!(all, vec![80, 70, 60, 50, 40, 30, 20]);
+
+    assert_eq!(tree.top_k(0).next(), None);
+}
+
+#[test]
+fn can_compute_quantiles_and_histograms() {
+    let mut tree = BinarySearchTree::new();
+    assert!(tree.quantiles(4).is_empty());
+    assert!(tree.histogram(4).is_empty());
+
+    for i in [7, 3, 9, 1, 5, 2, 8, 4, 6, 10, 11, 12] {
+        tree.insert(i);
+    }
+    tree.delete(&12);
+    tree.delete(&11);
+
+    assert_eq!(tree.quantiles(4), vec![&3, &5, &8]);
+    assert_eq!(tree.quantiles(2), vec![&5]);
+    assert!(tree.quantiles(1).is_empty());
+
+    let buckets = tree.histogram(3);
+    assert_eq!(buckets.len(), 3);
+    assert_eq!(
+        (buckets[0].first, buckets[0].last, buckets[0].count),
+        (&1, &3, 3)
+    );
+    assert_eq!(
+        (buckets[1].first, buckets[1].last, buckets[1].count),
+        (&4, &6, 3)
+    );
+    assert_eq!(
+        (buckets[2].first, buckets[2].last, buckets[2].count),
+        (&7, &10, 4)
+    );
+
+    assert_eq!(tree.histogram(20).len(), 10);
+}
+
+#[test]
+fn can_iterate_strided() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.iter_strided(3).next(), None);
+
+    for i in [5, 2, 8, 0, 3, 9, 1, 7, 4, 6] {
+        tree.insert(i);
+    }
+
+    let sampled: Vec<i32> = tree.iter_strided(3).copied().collect();
+    assert_eq!(sampled, vec![0, 3, 6, 9]);
+    assert_eq!(tree.iter_strided(3).size_hint(), (4, Some(4)));
+
+    let sampled: Vec<i32> = tree.iter_strided(1).copied().collect();
+    assert_eq!(sampled, vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
+}
+
+#[cfg(feature = "rand")]
+#[test]
+fn can_choose_random_items() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.choose(&mut rng), None);
+
+    for i in 0..10 {
+        tree.insert(i);
+    }
+    let mut counts = [0; 10];
+    for _ in 0..10_000 {
+        counts[*tree.choose(&mut rng).unwrap()] += 1;
+    }
+    assert!(counts.iter().all(|&count| (800..1200).contains(&count)));
+}
+
+#[cfg(feature = "rand")]
+#[test]
+fn can_iterate_shuffled() {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.iter_shuffled(&mut rng).next(), None);
+
+    for i in 0..100 {
+        tree.insert(i);
+    }
+
+    let shuffled: Vec<i32> = tree.iter_shuffled(&mut rng).copied().collect();
+    assert_ne!(shuffled, tree.iter_copied().collect::<Vec<_>>());
+
+    let mut sorted = shuffled.clone();
+    sorted.sort();
+    assert_eq!(sorted, (0..100).collect::<Vec<_>>());
+}
+
+#[test]
+fn matches_sorted_vec_under_random_operations() {
+    // Small linear congruential generator, so the test is deterministic.
+    let mut state = 0x2545_f491_u64;
+    let mut next = move || {
+        state = state.wrapping_mul(6364136223846793005).wrapping_add(1);
+        (state >> 33) as u32 % 200
+    };
+
+    let mut tree = BinarySearchTree::new();
+    let mut model: Vec<u32> = Vec::new();
+    for _ in 0..2000 {
+        let value = next();
+        if next() % 3 == 0 {
+            tree.delete(&value);
+            if let Some(pos) = model.iter().position(|&v| v == value) {
+                model.remove(pos);
+            }
+        } else {
+            tree.insert(value);
+            let pos = model.partition_point(|&v| v <= value);
+            model.insert(pos, value);
+        }
+    }
+
+    tree.check_invariants();
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), model);
+    assert_eq!(tree.iter_strided(1).copied().collect::<Vec<_>>(), model);
+    assert_eq!(
+        tree.top_k(usize::MAX).copied().collect::<Vec<_>>(),
+        model.iter().rev().copied().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn pooled_tree_reuses_freed_nodes() {
+    let mut tree = BinarySearchTree::new_pooled();
+    for i in 0..100 {
+        tree.insert(format!("item {:03}", i));
+    }
+    for i in (0..100).step_by(2) {
+        tree.delete(format!("item {:03}", i).as_str());
+    }
+    for i in 100..150 {
+        tree.insert(format!("item {:03}", i));
+    }
+
+    let items: Vec<String> = tree.iter_cloned().collect();
+    assert_eq!(items.len(), 100);
+    assert_eq!(items.first().unwrap(), "item 001");
+    assert_eq!(items.last().unwrap(), "item 149");
+    assert!(!tree.contains("item 050"));
+    assert!(tree.contains("item 051"));
+
+    let mut x = 42;
+    let mut tree = BinarySearchTree::new_pooled();
+    tree.insert(&mut x);
+
+    println!("{}", x);
+}
+
+#[test]
+fn can_round_trip_raw_parts() {
+    let mut tree = BinarySearchTree::new();
+    tree.insert(String::from("Hello"));
+    tree.insert(String::from("World"));
+
+    let (root, pool) = tree.into_raw_parts();
+    assert!(!root.is_null());
+    assert!(pool.is_null());
+    let tree: BinarySearchTree<String> = unsafe { BinarySearchTree::from_raw_parts(root, pool) };
+    assert!(tree.contains("Hello"));
+    assert!(tree.contains("World"));
+
+    let mut pooled = BinarySearchTree::new_pooled();
+    pooled.insert(3);
+    pooled.insert(44);
+
+    let (root, pool) = pooled.into_raw_parts();
+    assert!(!pool.is_null());
+    let mut pooled: BinarySearchTree<i32> = unsafe { BinarySearchTree::from_raw_parts(root, pool) };
+    pooled.insert(5);
+    assert_eq!(pooled.iter_copied().collect::<Vec<_>>(), vec![3, 5, 44]);
+
+    let (root, pool) = BinarySearchTree::<i32>::new().into_raw_parts();
+    assert!(root.is_null());
+    let empty: BinarySearchTree<i32> = unsafe { BinarySearchTree::from_raw_parts(root, pool) };
+    assert!(empty.min().is_none());
+}
+
+#[test]
+fn can_split_into_parts() {
+    assert!(BinarySearchTree::<i32>::new().into_parts().is_none());
+
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for i in [50, 30, 70, 20, 40, 60, 80] {
+            tree.insert(i.to_string());
+        }
+
+        let (left, root, right) = tree.into_parts().unwrap();
+        assert_eq!(root, "50");
+        assert_eq!(
+            left.iter_cloned().collect::<Vec<_>>(),
+            vec!["20", "30", "40"]
+        );
+        assert_eq!(
+            right.iter_cloned().collect::<Vec<_>>(),
+            vec!["60", "70", "80"]
+        );
+
+        let (_, root, right) = right.into_parts().unwrap();
+        assert_eq!(root, "70");
+        assert_eq!(right.iter_cloned().collect::<Vec<_>>(), vec!["80"]);
+        assert_eq!(right.quantiles(2), vec!["80"]);
+    }
+}
+
+#[test]
+fn can_join_and_split() {
+    let mut left = BinarySearchTree::new();
+    let mut right = BinarySearchTree::new_pooled();
+    for i in [2, 1, 3] {
+        left.insert(i);
+        right.insert(i + 10);
+    }
+
+    let tree = BinarySearchTree::join(left, 5, right);
+    assert_eq!(
+        tree.iter_copied().collect::<Vec<_>>(),
+        vec![1, 2, 3, 5, 11, 12, 13]
+    );
+    assert_eq!(tree.quantiles(2), vec![&5]);
+
+    let (less, found, greater) = tree.split(&3);
+    assert_eq!(found, Some(3));
+    assert_eq!(less.iter_copied().collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(
+        greater.iter_copied().collect::<Vec<_>>(),
+        vec![5, 11, 12, 13]
+    );
+    assert_eq!(greater.quantiles(4), vec![&5, &11, &12]);
+
+    let (less, found, greater) = greater.split(&7);
+    assert_eq!(found, None);
+    assert_eq!(less.iter_copied().collect::<Vec<_>>(), vec![5]);
+    assert_eq!(greater.iter_copied().collect::<Vec<_>>(), vec![11, 12, 13]);
+
+    let empty = BinarySearchTree::join(BinarySearchTree::new(), 1, BinarySearchTree::new());
+    assert_eq!(empty.iter_copied().collect::<Vec<_>>(), vec![1]);
+}
+
+#[test]
+#[should_panic]
+fn join_rejects_out_of_order_trees() {
+    let mut left = BinarySearchTree::new();
+    left.insert(10);
+    let _ = BinarySearchTree::join(left, 5, BinarySearchTree::new());
+}
+
+#[test]
+fn can_clone_range() {
+    let mut tree = BinarySearchTree::new();
+    for i in [50, 30, 70, 20, 40, 60, 80, 35, 45, 65] {
+        tree.insert(i);
+    }
+
+    let window = tree.clone_range(35..65);
+    assert_eq!(
+        window.iter_copied().collect::<Vec<_>>(),
+        vec![35, 40, 45, 50, 60]
+    );
+    assert_eq!(window.quantiles(2), vec![&45]);
+
+    let window = tree.clone_range(..=35);
+    assert_eq!(window.iter_copied().collect::<Vec<_>>(), vec![20, 30, 35]);
+
+    let window = tree.clone_range((Bound::Excluded(65), Bound::Unbounded));
+    assert_eq!(window.iter_copied().collect::<Vec<_>>(), vec![70, 80]);
+
+    assert!(tree.clone_range(90..).min().is_none());
+    assert_eq!(tree.iter_copied().count(), 10);
+
+    let mut strings = BinarySearchTree::new();
+    for word in ["apple", "banana", "cherry"] {
+        strings.insert(String::from(word));
+    }
+    let window = strings.clone_range::<str, _>((Bound::Included("b"), Bound::Excluded("c")));
+    assert_eq!(window.iter_cloned().collect::<Vec<_>>(), vec!["banana"]);
+}
+
+#[test]
+fn can_splice_ranges_between_trees() {
+    let mut tree = BinarySearchTree::new();
+    for i in [50, 30, 70, 20, 40, 60, 80, 35, 45, 65] {
+        tree.insert(i);
+    }
+
+    let mut other = BinarySearchTree::new();
+    assert_eq!(tree.splice_range(35..=45, &mut other), 3);
+    assert_eq!(
+        tree.iter_copied().collect::<Vec<_>>(),
+        vec![20, 30, 50, 60, 65, 70, 80]
+    );
+    assert_eq!(other.iter_copied().collect::<Vec<_>>(), vec![35, 40, 45]);
+
+    // Goes past the end of the other tree.
+    assert_eq!(tree.splice_range(60..70, &mut other), 2);
+    assert_eq!(
+        other.iter_copied().collect::<Vec<_>>(),
+        vec![35, 40, 45, 60, 65]
+    );
+
+    // Goes before the start of the other tree.
+    assert_eq!(tree.splice_range(..=20, &mut other), 1);
+    assert_eq!(
+        other.iter_copied().collect::<Vec<_>>(),
+        vec![20, 35, 40, 45, 60, 65]
+    );
+
+    // Interleaves with the other tree.
+    let mut pooled = BinarySearchTree::new_pooled();
+    for i in [10, 25, 42, 55, 90] {
+        pooled.insert(i);
+    }
+    assert_eq!(other.splice_range(.., &mut pooled), 6);
+    assert!(other.min().is_none());
+    assert_eq!(
+        pooled.iter_copied().collect::<Vec<_>>(),
+        vec![10, 20, 25, 35, 40, 42, 45, 55, 60, 65, 90]
+    );
+    assert_eq!(pooled.quantiles(2), vec![&42]);
+
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![30, 50, 70, 80]);
+    assert_eq!(tree.quantiles(4), vec![&30, &50, &70]);
+    assert_eq!(tree.splice_range(100.., &mut pooled), 0);
+}
+
+#[test]
+fn can_subtract_trees() {
+    let mut tree = BinarySearchTree::new();
+    for i in [50, 30, 70, 20, 40, 60, 80, 30, 45] {
+        tree.insert(i.to_string());
+    }
+
+    let mut other = BinarySearchTree::new();
+    for i in [10, 30, 45, 46, 80, 90] {
+        other.insert(i.to_string());
+    }
+
+    tree.subtract(&other);
+    assert_eq!(
+        tree.iter_cloned().collect::<Vec<_>>(),
+        vec!["20", "40", "50", "60", "70"]
+    );
+    assert_eq!(tree.quantiles(2), vec!["50"]);
+
+    let mut small = BinarySearchTree::new();
+    small.insert(String::from("50"));
+    tree -= &small;
+    assert_eq!(
+        tree.iter_cloned().collect::<Vec<_>>(),
+        vec!["20", "40", "60", "70"]
+    );
+
+    tree -= &BinarySearchTree::new();
+    assert_eq!(tree.iter_cloned().count(), 4);
+}
+
+#[test]
+fn can_retain_common_items() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for i in [50, 30, 70, 20, 40, 60, 80, 40, 45] {
+            tree.insert(i.to_string());
+        }
+
+        let mut other = BinarySearchTree::new();
+        for i in [35, 40, 46, 50, 65, 70] {
+            other.insert(i.to_string());
+        }
+
+        tree.retain_common(&other);
+        assert_eq!(
+            tree.iter_cloned().collect::<Vec<_>>(),
+            vec!["40", "40", "50", "70"]
+        );
+        assert_eq!(tree.quantiles(2), vec!["40"]);
+
+        tree.insert(String::from("10"));
+        tree.retain_common(&BinarySearchTree::new());
+        assert!(tree.min().is_none());
+    }
+}
+
+#[test]
+fn can_build_from_sorted_streams() {
+    for n in 0..70 {
+        let tree = BinarySearchTree::from_sorted_iter(0..n);
+        assert_eq!(
+            tree.iter_copied().collect::<Vec<_>>(),
+            (0..n).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            tree.iter_strided(1).copied().collect::<Vec<_>>(),
+            (0..n).collect::<Vec<_>>()
+        );
+    }
+
+    let input = "apple\nbanana\r\nbanana\ncherry\n";
+    let tree = BinarySearchTree::from_sorted_reader(input.as_bytes(), |line| {
+        Ok::<_, std::io::Error>(line.to_string())
+    })
+    .unwrap();
+    assert_eq!(
+        tree.iter_cloned().collect::<Vec<_>>(),
+        vec!["apple", "banana", "banana", "cherry"]
+    );
+
+    let numbers = "1\n2\nthree\n4\n";
+    let result = BinarySearchTree::<u32>::from_sorted_reader(numbers.as_bytes(), |line| {
+        line.parse::<u32>()
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    });
+    assert!(result.is_err());
+}
+
+#[test]
+#[should_panic(expected = "sorted order")]
+fn sorted_builds_reject_unsorted_input() {
+    let _ = BinarySearchTree::from_sorted_iter([String::from("b"), String::from("a")]);
+}
+
+#[cfg(not(feature = "node-ids"))]
+#[test]
+fn debug_prints_like_a_set_inside_other_types() {
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    struct Scores {
+        name: &'static str,
+        seen: BinarySearchTree<u32>,
+    }
+
+    let scores = Scores {
+        name: "a",
+        seen: BinarySearchTree::from([5, 1, 3]),
+    };
+    assert_eq!(
+        format!("{:?}", scores),
+        "Scores { name: \"a\", seen: {1, 3, 5} }"
+    );
+    assert_eq!(
+        format!("{:#?}", scores.seen),
+        "{\n    1,\n    3,\n    5,\n}"
+    );
+    assert_eq!(format!("{:?}", BinarySearchTree::<u32>::new()), "{}");
+}
+
+#[test]
+fn can_debug_print_and_graph() {
+    let mut tree = BinarySearchTree::new();
+    tree.insert(2);
+    tree.insert(1);
+    tree.insert(3);
+    tree.check_invariants();
+
+    #[cfg(not(feature = "node-ids"))]
+    {
+        assert_eq!(format!("{:?}", tree), "{1, 2, 3}");
+        assert_eq!(
+            tree.to_dot(),
+            "digraph {\n    n0 [label=\"2\"];\n    n1 [label=\"1\"];\n    n0 -> n1 [label=\"left\"];\n    n2 [label=\"3\"];\n    n0 -> n2 [label=\"right\"];\n}\n"
+        );
+    }
+    #[cfg(feature = "node-ids")]
+    {
+        assert_eq!(format!("{:?}", tree), "{1 (#1), 2 (#0), 3 (#2)}");
+        assert!(tree.to_dot().contains("n0 [label=\"2 (#0)\"];"));
+
+        tree.delete(&2);
+        tree.insert(2);
+        assert_eq!(format!("{:?}", tree), "{1 (#1), 2 (#3), 3 (#2)}");
+
+        let (root, pool) = tree.into_raw_parts();
+        let mut tree = unsafe { BinarySearchTree::from_raw_parts(root, pool) };
+        tree.insert(4);
+        assert_eq!(format!("{:?}", tree), "{1 (#1), 2 (#3), 3 (#2), 4 (#4)}");
+    }
+
+    let strings = BinarySearchTree::from_sorted_iter([String::from("say \"hi\"")]);
+    assert!(strings.to_dot().contains(r#"label="\"say \\\"hi\\\"\""#));
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn can_record_and_replay_traces() {
+    use binarysearchtree::{Op, Traced};
+
+    let mut traced = Traced::new_pooled();
+    for value in [5, 2, 8, 2, 9, 1] {
+        traced.insert(value);
+    }
+    traced.delete(&2);
+    traced.subtract(&BinarySearchTree::from_sorted_iter([9]));
+    traced.retain_common(&BinarySearchTree::from_sorted_iter([1, 2, 5, 8]));
+    assert_eq!(traced.iter_copied().collect::<Vec<_>>(), vec![1, 2, 5, 8]);
+    assert_eq!(traced.trace().ops[6], Op::Delete(2));
+
+    let replayed = BinarySearchTree::replay(traced.trace());
+    assert_eq!(
+        replayed.iter_copied().collect::<Vec<_>>(),
+        traced.iter_copied().collect::<Vec<_>>()
+    );
+
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(traced.trace()).unwrap();
+        let trace: binarysearchtree::Trace<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(&trace, traced.trace());
+    }
+
+    let (tree, trace) = traced.into_parts();
+    assert_eq!(trace.ops.len(), 9);
+    assert_eq!(tree.min(), Some(&1));
+}
+
+#[cfg(feature = "trace")]
+#[test]
+fn every_mutating_call_is_traced_and_replayed() {
+    use binarysearchtree::{DuplicatePolicy, Traced};
+
+    let mut traced = Traced::new();
+    traced.extend([50, 10, 90, 30, 70]);
+    traced.extend(&[20, 20]);
+    traced.insert(40);
+    assert!(!traced.insert_unique(40));
+    assert!(traced.try_insert(60).is_ok());
+    assert_eq!(traced.replace(60), Some(60));
+    assert_eq!(*traced.get_or_insert_with(&80, || 80), 80);
+    assert_eq!(*traced.get_or_insert_with(&80, || unreachable!()), 80);
+    traced.insert_range(100..105);
+    traced.delete(&10);
+    assert_eq!(traced.remove(&20), Some(20));
+    assert_eq!(traced.take(&21), None);
+    assert_eq!(traced.remove_range(101..103), 2);
+    assert_eq!(traced.drain_range(..=20).collect::<Vec<_>>(), vec![20]);
+    traced.retain(|item| item % 20 != 0);
+    assert_eq!(traced.extract_if(|item| *item > 60).next(), Some(70));
+    traced.subtract(&BinarySearchTree::from_sorted_iter([30]));
+    traced -= &BinarySearchTree::from_sorted_iter([50]);
+    traced.retain_common(&BinarySearchTree::from_sorted_iter([90, 100, 103, 104]));
+    let mut other = BinarySearchTree::from_sorted_iter([1, 2]);
+    assert_eq!(traced.splice_range(..100, &mut other), 1);
+    traced.append(&mut other);
+    assert_eq!(traced.split_off(&104).len(), 1);
+    traced.compact();
+    let mut traced = traced.with_duplicates(DuplicatePolicy::Reject);
+    {
+        let mut cursor = traced.cursor_front_mut();
+        assert_eq!(cursor.remove_current(), Some(1));
+        assert_eq!(cursor.insert_before(0), Ok(()));
+        assert_eq!(cursor.insert_after(2), Err(2));
+    }
+    {
+        let mut cursor = traced.cursor_back_mut();
+        cursor.move_next();
+        assert_eq!(cursor.insert_after(-1), Ok(()));
+    }
+    assert_eq!(traced.cursor_at_mut(&100).insert_before(101), Ok(()));
+    assert_eq!(
+        traced.iter_copied().collect::<Vec<_>>(),
+        vec![-1, 0, 2, 90, 101, 103]
+    );
+
+    let replayed = BinarySearchTree::replay(traced.trace());
+    assert_eq!(replayed, *traced);
+    assert_eq!(replayed.duplicate_policy(), DuplicatePolicy::Reject);
+
+    traced.drain().next();
+    traced.clear();
+    assert_eq!(traced.trace().ops.len(), 31);
+    assert!(BinarySearchTree::replay(traced.trace()).is_empty());
+}
+
+#[cfg(feature = "stats")]
+#[test]
+fn can_profile_operations() {
+    let mut tree = BinarySearchTree::new();
+    for value in 1..=4 {
+        tree.insert(value);
+    }
+    assert!(tree.contains(&4));
+    assert!(!tree.contains(&0));
+    tree.delete(&1);
+    tree.subtract(&BinarySearchTree::from_sorted_iter([2]));
+
+    let stats = tree.stats();
+    assert_eq!(stats.inserts, 4);
+    assert_eq!(stats.lookups, 2);
+    assert_eq!(stats.deletes, 1);
+    assert_eq!(stats.rebalances, 1);
+    assert_eq!(stats.paths, 7);
+    // Inserting 1..=4 in order builds a path, so each insert passes every earlier item.
+    assert_eq!(stats.total_path_len, 6 + 4 + 1 + 1);
+    assert_eq!(stats.max_path_len, 4);
+    assert_eq!(stats.average_path_len(), 12.0 / 7.0);
+
+    tree.reset_stats();
+    assert_eq!(tree.stats(), Default::default());
+}
+
+#[test]
+fn can_watch_key_ranges() {
+    use binarysearchtree::{Change, Watched};
+    use std::{cell::RefCell, rc::Rc};
+
+    let mut tree = Watched::new(BinarySearchTree::from_sorted_iter([1, 5]));
+    let seen = Rc::new(RefCell::new(Vec::new()));
+    let log = Rc::clone(&seen);
+    let id = tree.watch(3..=6, move |change| log.borrow_mut().push(change.cloned()));
+    let (_, changes) = tree.watch_channel(..4);
+
+    tree.insert(4);
+    tree.insert(2);
+    tree.delete(&5);
+    tree.delete(&7);
+    assert!(tree.unwatch(id));
+    assert!(!tree.unwatch(id));
+    tree.insert(3);
+
+    assert_eq!(*seen.borrow(), vec![Change::Entered(4), Change::Left(5)]);
+    assert_eq!(
+        changes.try_iter().collect::<Vec<_>>(),
+        vec![Change::Entered(2), Change::Entered(3)]
+    );
+    assert_eq!(
+        tree.into_inner().iter_copied().collect::<Vec<_>>(),
+        vec![1, 2, 3, 4]
+    );
+}
+
+#[test]
+fn watched_trees_notify_removals_by_borrowed_key_and_clears() {
+    use binarysearchtree::{Change, Watched};
+
+    let mut watched = Watched::new(BinarySearchTree::from_sorted_iter(
+        ["a", "b", "c"].map(String::from),
+    ));
+    let (_, changes) = watched.watch_channel(String::from("b")..);
+    assert_eq!(watched.remove("b"), Some(String::from("b")));
+    assert_eq!(watched.take("a"), Some(String::from("a")));
+    assert_eq!(watched.remove("b"), None);
+    watched.insert(String::from("d"));
+    watched.clear();
+    assert!(watched.is_empty());
+    assert_eq!(
+        changes.try_iter().collect::<Vec<_>>(),
+        vec![
+            Change::Left(String::from("b")),
+            Change::Entered(String::from("d")),
+            Change::Left(String::from("c")),
+            Change::Left(String::from("d")),
+        ]
+    );
+}
+
+#[test]
+fn can_purge_expired_entries() {
+    use binarysearchtree::ExpiringMap;
+
+    let mut cache = ExpiringMap::new();
+    assert_eq!(cache.insert("a", 1, 30), None);
+    assert_eq!(cache.insert("b", 2, 10), None);
+    assert_eq!(cache.insert("c", 3, 20), None);
+    assert_eq!(cache.insert("a", 4, 5), Some(1));
+    assert_eq!(cache.len(), 3);
+    assert_eq!(cache.deadline(&"a"), Some(&5));
+    assert_eq!(cache.next_deadline(), Some(&5));
+
+    assert_eq!(cache.purge_expired(&10), vec![("a", 4), ("b", 2)]);
+    assert_eq!(cache.get(&"a"), None);
+    assert_eq!(cache.get(&"c"), Some(&3));
+    assert_eq!(cache.purge_expired(&15), vec![]);
+
+    assert_eq!(cache.remove(&"c"), Some(3));
+    assert!(cache.is_empty());
+    assert_eq!(cache.next_deadline(), None);
+}
+
+#[test]
+fn bounded_trees_apply_eviction_policy() {
+    use binarysearchtree::{BoundedTree, EvictionPolicy};
+
+    let mut top = BoundedTree::new(3, EvictionPolicy::EvictMin);
+    let evicted = [5, 1, 8, 3, 9, 2]
+        .into_iter()
+        .filter_map(|value| top.insert(value))
+        .collect::<Vec<_>>();
+    assert_eq!(evicted, vec![1, 3, 2]);
+    assert_eq!(top.iter_copied().collect::<Vec<_>>(), vec![5, 8, 9]);
+
+    let mut bottom = BoundedTree::new(2, EvictionPolicy::EvictMax);
+    assert_eq!(bottom.insert(5), None);
+    assert_eq!(bottom.insert(7), None);
+    assert_eq!(bottom.insert(6), Some(7));
+    assert_eq!(bottom.insert(9), Some(9));
+    assert_eq!(bottom.iter_copied().collect::<Vec<_>>(), vec![5, 6]);
+
+    let mut first = BoundedTree::new(1, EvictionPolicy::RejectNew);
+    assert_eq!(first.insert(2), None);
+    assert_eq!(first.insert(1), Some(1));
+    first.delete(&2);
+    assert_eq!(first.insert(1), None);
+    assert_eq!(first.into_inner().min(), Some(&1));
+
+    let mut empty = BoundedTree::new(0, EvictionPolicy::EvictMin);
+    assert_eq!(empty.insert(1), Some(1));
+    assert!(empty.min().is_none());
+}
+
+#[test]
+fn bounded_trees_can_remove_by_borrowed_key_and_clear() {
+    use binarysearchtree::{BoundedTree, EvictionPolicy};
+
+    let mut bounded = BoundedTree::new(2, EvictionPolicy::RejectNew);
+    bounded.insert(String::from("a"));
+    bounded.insert(String::from("b"));
+    assert_eq!(bounded.insert(String::from("c")), Some(String::from("c")));
+    assert_eq!(bounded.remove("a"), Some(String::from("a")));
+    assert_eq!(bounded.insert(String::from("c")), None);
+    assert_eq!(bounded.take("b"), Some(String::from("b")));
+    bounded.clear();
+    assert!(bounded.is_empty());
+    assert_eq!(bounded.insert(String::from("d")), None);
+    assert_eq!(bounded.insert(String::from("e")), None);
+    assert_eq!(bounded.insert(String::from("f")), Some(String::from("f")));
+}
+
+#[test]
+fn can_split_into_chunks() {
+    let tree = BinarySearchTree::from_sorted_iter(0..10);
+    let chunks = tree
+        .chunks(3)
+        .into_iter()
+        .map(|chunk| chunk.copied().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(chunks, vec![vec![0, 1, 2], vec![3, 4, 5], vec![6, 7, 8, 9]]);
+    assert_eq!(tree.partition_points(3), vec![&3, &6]);
+
+    let small = BinarySearchTree::from_sorted_iter([1, 2]);
+    assert_eq!(small.chunks(4).len(), 2);
+    assert_eq!(small.partition_points(4), vec![&2]);
+    assert!(BinarySearchTree::<i32>::new().chunks(2).is_empty());
+    assert!(tree.chunks(0).is_empty());
+
+    let mut unbalanced = BinarySearchTree::new();
+    for value in [5, 3, 8, 1, 4, 7, 9, 2, 6] {
+        unbalanced.insert(value);
+    }
+    for n in 1..=10 {
+        let joined = unbalanced
+            .chunks(n)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(joined, (1..=9).collect::<Vec<_>>());
+    }
+}
+
+#[test]
+fn content_hash_ignores_order_and_shape() {
+    use binarysearchtree::Hashed;
+
+    let mut a = Hashed::default();
+    let mut b = Hashed::from(BinarySearchTree::from_sorted_iter([1, 2, 2, 3]));
+    for value in [3, 2, 1] {
+        a.insert(value);
+    }
+    assert_ne!(a.content_hash(), b.content_hash());
+    a.insert(2);
+    assert_eq!(a.content_hash(), b.content_hash());
+
+    b.delete(&2);
+    b.delete(&4);
+    a.delete(&2);
+    assert_eq!(a.content_hash(), b.content_hash());
+    b.delete(&2);
+    assert_ne!(a.content_hash(), b.content_hash());
+
+    for value in [1, 3] {
+        a.delete(&value);
+        b.delete(&value);
+    }
+    a.delete(&2);
+    assert_eq!(a.content_hash(), 0);
+    assert_eq!(b.into_inner().min(), None);
+}
+
+#[test]
+fn hashed_trees_can_remove_by_borrowed_key_and_clear() {
+    use binarysearchtree::Hashed;
+
+    let mut hashed = Hashed::from(BinarySearchTree::from_sorted_iter(
+        ["a", "b", "c"].map(String::from),
+    ));
+    let expected = Hashed::from(BinarySearchTree::from_sorted_iter([String::from("c")]));
+    assert_eq!(hashed.remove("a"), Some(String::from("a")));
+    assert_eq!(hashed.take("b"), Some(String::from("b")));
+    assert_eq!(hashed.take("z"), None);
+    assert_eq!(hashed.content_hash(), expected.content_hash());
+    hashed.clear();
+    assert!(hashed.is_empty());
+    assert_eq!(hashed.content_hash(), 0);
+}
+
+#[test]
+fn can_explain_searches() {
+    use binarysearchtree::{Comparison, Direction};
+
+    let mut tree = BinarySearchTree::new();
+    for value in [5, 2, 8, 3] {
+        tree.insert(value);
+    }
+    let step = |item, direction| Comparison { item, direction };
+    assert_eq!(
+        tree.explain(&3),
+        vec![
+            step(&5, Direction::Left),
+            step(&2, Direction::Right),
+            step(&3, Direction::Found)
+        ]
+    );
+    assert_eq!(
+        tree.explain(&9),
+        vec![step(&5, Direction::Right), step(&8, Direction::Right)]
+    );
+    assert!(BinarySearchTree::<i32>::new().explain(&1).is_empty());
+}
+
+#[cfg(feature = "events")]
+#[test]
+fn can_record_structural_events() {
+    use binarysearchtree::{record_events, Event, Side};
+
+    let mut tree = BinarySearchTree::new();
+    let ((), events) = record_events(|| {
+        tree.insert(5);
+        tree.insert(2);
+    });
+    assert_eq!(
+        events,
+        vec![
+            Event::Attach {
+                node: 0,
+                parent: None
+            },
+            Event::Compare { node: 0 },
+            Event::Descend {
+                from: 0,
+                side: Side::Left
+            },
+            Event::Attach {
+                node: 1,
+                parent: Some((0, Side::Left))
+            },
+        ]
+    );
+
+    tree.insert(8);
+    tree.insert(7);
+    let ((), events) = record_events(|| tree.delete(&5));
+    assert_eq!(
+        events,
+        vec![
+            Event::Compare { node: 0 },
+            Event::ReplaceWithSuccessor {
+                node: 0,
+                successor: 3
+            },
+        ]
+    );
+
+    let (found, events) = record_events(|| tree.contains(&9));
+    assert!(!found);
+    assert_eq!(
+        events,
+        vec![
+            Event::Compare { node: 3 },
+            Event::Descend {
+                from: 3,
+                side: Side::Right
+            },
+            Event::Compare { node: 2 },
+            Event::Descend {
+                from: 2,
+                side: Side::Right
+            },
+        ]
+    );
+
+    let ((), events) = record_events(|| tree.delete(&2));
+    assert_eq!(
+        events.last(),
+        Some(&Event::Replace {
+            node: 1,
+            with: None
+        })
+    );
+    assert!(record_events(|| ()).1.is_empty());
+}
+
+#[test]
+fn can_drain_ranges() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for value in [5, 2, 8, 1, 9, 3, 7, 4, 6] {
+            tree.insert(value.to_string());
+        }
+        let drained = tree.drain_range::<str, _>((Bound::Included("3"), Bound::Excluded("7")));
+        assert_eq!(drained.len(), 4);
+        assert_eq!(drained.collect::<Vec<_>>(), vec!["3", "4", "5", "6"]);
+        tree.check_invariants();
+        assert_eq!(
+            tree.iter_cloned().collect::<Vec<_>>(),
+            vec!["1", "2", "7", "8", "9"]
+        );
+
+        let mut partial = tree.drain_range::<str, _>((Bound::Excluded("1"), Bound::Unbounded));
+        assert_eq!(partial.next().as_deref(), Some("2"));
+        drop(partial);
+        assert_eq!(tree.iter_cloned().collect::<Vec<_>>(), vec!["1"]);
+        assert_eq!(tree.drain_range::<str, _>(..).count(), 1);
+        assert!(tree.min().is_none());
+    }
+}
+
+#[test]
+fn can_remove_ranges() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for value in [5, 2, 8, 1, 9, 3, 7, 4, 6, 4] {
+            tree.insert(value.to_string());
+        }
+        assert_eq!(
+            tree.remove_range::<str, _>((Bound::Included("3"), Bound::Excluded("7"))),
+            5
+        );
+        tree.check_invariants();
+        assert_eq!(
+            tree.iter_cloned().collect::<Vec<_>>(),
+            vec!["1", "2", "7", "8", "9"]
+        );
+        assert_eq!(
+            tree.remove_range::<str, _>((Bound::Excluded("9"), Bound::Unbounded)),
+            0
+        );
+        assert_eq!(tree.remove_range::<str, _>(..), 5);
+        assert!(tree.is_empty());
+    }
+}
+
+#[test]
+fn can_drain_whole_tree() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for value in [5, 2, 8, 1, 9, 3, 7, 4, 6] {
+            tree.insert(value.to_string());
+        }
+        let mut drained = tree.drain();
+        assert_eq!(drained.len(), 9);
+        assert_eq!(drained.next().as_deref(), Some("1"));
+        assert_eq!(drained.next_back().as_deref(), Some("9"));
+        drop(drained);
+        assert!(tree.min().is_none());
+
+        tree.insert("10".to_string());
+        assert_eq!(tree.drain().collect::<Vec<_>>(), vec!["10"]);
+        assert_eq!(tree.drain().count(), 0);
+    }
+}
+
+#[test]
+fn can_extract_matching_items() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for value in [5, 2, 8, 1, 9, 3, 7, 4, 6, 4] {
+            tree.insert(value);
+        }
+        let evens = tree.extract_if(|n| n % 2 == 0).collect::<Vec<_>>();
+        assert_eq!(evens, vec![2, 4, 4, 6, 8]);
+        tree.check_invariants();
+        assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 3, 5, 7, 9]);
+
+        assert_eq!(tree.extract_if(|n| *n > 2).next(), Some(3));
+        tree.check_invariants();
+        assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 5, 7, 9]);
+    }
+}
+
+#[test]
+fn can_extract_runs_from_a_degenerate_tree() {
+    // Inserting in order leaves every node with only a right child.
+    let mut tree = BinarySearchTree::new();
+    for value in 0..2000 {
+        tree.insert(value);
+    }
+    let extracted = tree.extract_if(|n| n % 7 < 3).collect::<Vec<_>>();
+    assert_eq!(
+        extracted,
+        (0..2000).filter(|n| n % 7 < 3).collect::<Vec<_>>()
+    );
+    tree.check_invariants();
+    assert_eq!(
+        tree.iter_copied().collect::<Vec<_>>(),
+        (0..2000).filter(|n| n % 7 >= 3).collect::<Vec<_>>()
+    );
+
+    let mut extract = tree.extract_if(|n| n % 7 == 6);
+    assert_eq!(extract.next(), Some(6));
+    assert_eq!(extract.next(), Some(13));
+    assert_eq!(tree.len(), 2000 - extracted.len() - 2);
+    tree.check_invariants();
+}
+
+#[test]
+fn can_iterate_over_ranges() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.range(1..5).next(), None);
+
+    for value in [5, 2, 8, 1, 9, 3, 7, 4, 6, 4] {
+        tree.insert(value);
+    }
+    assert_eq!(
+        tree.range(3..6).copied().collect::<Vec<_>>(),
+        vec![3, 4, 4, 5]
+    );
+    assert_eq!(tree.range(..=2).copied().collect::<Vec<_>>(), vec![1, 2]);
+    assert_eq!(tree.range(8..).copied().collect::<Vec<_>>(), vec![8, 9]);
+    assert_eq!(tree.range(..).len(), 10);
+    assert_eq!(
+        tree.range((Bound::Excluded(4), Bound::Excluded(7)))
+            .rev()
+            .copied()
+            .collect::<Vec<_>>(),
+        vec![6, 5]
+    );
+    assert_eq!(tree.range(20..30).next(), None);
+    assert_eq!(
+        tree.range((Bound::Excluded(5), Bound::Excluded(5))).next(),
+        None
+    );
+
+    let mut words = BinarySearchTree::new();
+    for word in ["apple", "banana", "cherry"] {
+        words.insert(word.to_string());
+    }
+    let middle = words
+        .range::<str, _>((Bound::Included("b"), Bound::Excluded("c")))
+        .collect::<Vec<_>>();
+    assert_eq!(middle, vec!["banana"]);
+}
+
+#[test]
+fn can_count_items_in_ranges() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.range_count(..), 0);
+
+    for value in [5, 2, 8, 1, 9, 3, 7, 4, 6, 4] {
+        tree.insert(value);
+    }
+    assert_eq!(tree.range_count(3..6), 4);
+    assert_eq!(tree.range_count(..=4), 5);
+    assert_eq!(tree.range_count(10..), 0);
+    assert_eq!(tree.range_count(..), 10);
+    assert_eq!(
+        tree.range_count((Bound::Excluded(4), Bound::Excluded(4))),
+        0
+    );
+}
+
+#[test]
+fn can_iterate_from_a_key() {
+    let mut tree = BinarySearchTree::new();
+    for value in [5, 2, 8, 1, 9, 3, 7, 4, 6, 4] {
+        tree.insert(value.to_string());
+    }
+    assert_eq!(tree.iter_from("7").collect::<Vec<_>>(), vec!["7", "8", "9"]);
+    assert_eq!(tree.iter_after("7").collect::<Vec<_>>(), vec!["8", "9"]);
+    assert_eq!(tree.iter_from("35").collect::<Vec<_>>().len(), 7);
+    assert_eq!(tree.iter_from("4").len(), 7);
+    assert_eq!(tree.iter_after("4").next().map(String::as_str), Some("5"));
+    assert_eq!(tree.iter_after("9").next(), None);
+}
+
+#[test]
+fn iterators_report_exact_sizes() {
+    fn assert_exact<I: ExactSizeIterator + FusedIterator>(mut iter: I) {
+        let mut len = iter.len();
+        assert_eq!(iter.size_hint(), (len, Some(len)));
+        while iter.next().is_some() {
+            len -= 1;
+            assert_eq!(iter.len(), len);
+        }
+        assert_eq!(len, 0);
+        assert!(iter.next().is_none());
+    }
+
+    let mut tree = BinarySearchTree::new();
+    for value in [5, 2, 8, 1, 9, 3, 7, 4, 6, 4] {
+        tree.insert(value);
+    }
+    assert_exact(tree.iter());
+    assert_exact(tree.range(2..7));
+    assert_exact(tree.iter_pairs());
+    assert_exact(tree.top_k(3));
+    assert_exact(tree.top_k(20));
+    assert_exact(tree.iter_strided(3));
+    assert_exact(tree.level_order());
+    assert_exact(tree.level_order().with_levels());
+    assert_exact(tree.zigzag());
+    assert_exact(tree.pre_order());
+    assert_exact(tree.post_order());
+    assert_exact(tree.clone_range(..).into_iter());
+    assert_exact(tree.drain_range(3..5));
+    assert_eq!(tree.top_k(20).len(), 7);
+    assert_eq!(tree.group_by_equal().size_hint(), (1, None));
+}
+
+#[test]
+fn can_move_cursors_around() {
+    let empty = BinarySearchTree::<i32>::new();
+    assert_eq!(empty.cursor_front().current(), None);
+    assert_eq!(empty.cursor_back().peek_next(), None);
+
+    let mut tree = BinarySearchTree::new();
+    for value in [5, 2, 8, 1, 3, 9, 4] {
+        tree.insert(value);
+    }
+    let mut cursor = tree.cursor_front();
+    assert_eq!(cursor.current(), Some(&1));
+    assert_eq!(cursor.depth(), Some(2));
+    assert_eq!(cursor.peek_prev(), None);
+    assert_eq!(cursor.peek_next(), Some(&2));
+    cursor.move_prev();
+    assert_eq!(cursor.current(), None);
+    assert_eq!(cursor.depth(), None);
+    cursor.move_prev();
+    assert_eq!(cursor.current(), Some(&9));
+    cursor.move_next();
+    cursor.move_next();
+    assert_eq!(cursor.current(), Some(&1));
+
+    let mut cursor = tree.cursor_at(&4);
+    assert_eq!((cursor.current(), cursor.depth()), (Some(&4), Some(3)));
+    cursor.move_next();
+    assert_eq!((cursor.current(), cursor.depth()), (Some(&5), Some(0)));
+
+    let cursor = tree.cursor_at(&6);
+    assert_eq!(cursor.current(), Some(&8));
+    assert_eq!(cursor.peek_prev(), Some(&5));
+    assert_eq!(tree.cursor_at(&10).current(), None);
+    assert_eq!(tree.cursor_back().current(), Some(&9));
+}
+
+#[test]
+fn can_edit_through_cursors() {
+    let mut tree = BinarySearchTree::new();
+    for value in [5, 2, 8, 1, 3, 9] {
+        tree.insert(value);
+    }
+    let mut cursor = tree.cursor_at_mut(&3);
+    assert_eq!(cursor.current(), Some(&3));
+    assert_eq!(cursor.insert_after(4), Ok(()));
+    assert_eq!(cursor.insert_after(6), Err(6));
+    assert_eq!(cursor.insert_before(3), Ok(()));
+    assert_eq!(cursor.insert_before(1), Err(1));
+    assert_eq!(cursor.current(), Some(&3));
+    assert_eq!(cursor.peek_prev(), Some(&3));
+    assert_eq!(cursor.peek_next(), Some(&4));
+    assert_eq!(cursor.as_cursor().depth(), Some(2));
+
+    assert_eq!(cursor.remove_current(), Some(3));
+    assert_eq!(cursor.current(), Some(&4));
+    cursor.move_next();
+    cursor.move_next();
+    assert_eq!(cursor.remove_current(), Some(8));
+    assert_eq!(cursor.remove_current(), Some(9));
+    assert_eq!(cursor.current(), None);
+    assert_eq!(cursor.remove_current(), None);
+    assert_eq!(cursor.insert_before(10), Ok(()));
+    assert_eq!(cursor.insert_after(0), Ok(()));
+    assert_eq!(cursor.current(), None);
+    assert_eq!(cursor.peek_next(), Some(&0));
+    assert_eq!(cursor.peek_prev(), Some(&10));
+    tree.check_invariants();
+    assert_eq!(
+        tree.iter_copied().collect::<Vec<_>>(),
+        vec![0, 1, 2, 3, 4, 5, 10]
+    );
+
+    let mut cursor = tree.cursor_back_mut();
+    assert_eq!(cursor.remove_current(), Some(10));
+    cursor.move_prev();
+    assert_eq!(cursor.current(), Some(&5));
+    let mut cursor = tree.cursor_front_mut();
+    cursor.move_prev();
+    assert_eq!(cursor.current(), None);
+    assert_eq!(cursor.insert_before(4), Err(4));
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn can_iterate_in_parallel() {
+    use rayon::prelude::*;
+
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.par_iter().count(), 0);
+
+    // Inserting in order makes the tree a single long path, which still splits evenly.
+    for i in 0..2_000u64 {
+        tree.insert(i);
+    }
+    assert_eq!(tree.par_iter().len(), 2_000);
+    assert_eq!(tree.par_iter().sum::<u64>(), 1_999_000);
+    assert_eq!(
+        (&tree).into_par_iter().copied().collect::<Vec<_>>(),
+        (0..2_000).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        tree.par_iter()
+            .skip(100)
+            .take(3)
+            .copied()
+            .collect::<Vec<_>>(),
+        vec![100, 101, 102]
+    );
+}
+
+#[test]
+fn can_measure_height() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.height(), 0);
+    tree.insert(5);
+    assert_eq!(tree.height(), 1);
+    for value in [2, 8, 1, 3, 4] {
+        tree.insert(value);
+    }
+    assert_eq!(tree.height(), 4);
+
+    let mut chain = BinarySearchTree::new();
+    for value in 0..1000 {
+        chain.insert(value);
+    }
+    assert_eq!(chain.height(), 1000);
+    assert_eq!(BinarySearchTree::from_sorted_iter(0..1000).height(), 10);
+}
+
+#[test]
+fn can_find_depths_of_keys() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.depth_of(&5), None);
+    for value in [5, 2, 8, 1, 3, 4] {
+        tree.insert(value);
+    }
+    assert_eq!(tree.depth_of(&5), Some(0));
+    assert_eq!(tree.depth_of(&8), Some(1));
+    assert_eq!(tree.depth_of(&4), Some(3));
+    assert_eq!(tree.depth_of(&6), None);
+}
+
+#[test]
+fn can_select_by_rank() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.select(0), None);
+    for value in [50, 30, 70, 20, 40, 60, 80, 40] {
+        tree.insert(value);
+    }
+    assert_eq!(tree.select(0), Some(&20));
+    assert_eq!(tree.select(2), Some(&40));
+    assert_eq!(tree.select(3), Some(&40));
+    assert_eq!(tree.select(7), Some(&80));
+    assert_eq!(tree.select(8), None);
+
+    assert_eq!(tree.kth_largest(0), Some(&80));
+    assert_eq!(tree.kth_largest(3), Some(&50));
+    assert_eq!(tree.kth_largest(4), Some(&40));
+    assert_eq!(tree.kth_largest(7), Some(&20));
+    assert_eq!(tree.kth_largest(8), None);
+}
+
+#[test]
+fn can_index_by_rank() {
+    let tree = BinarySearchTree::from([50, 30, 70, 20]);
+    assert_eq!(tree[0], 20);
+    assert_eq!(tree[3], 70);
+}
+
+#[test]
+#[should_panic(expected = "rank 4 is out of bounds for a tree of 4 items")]
+fn indexing_past_the_end_panics() {
+    let tree = BinarySearchTree::from([50, 30, 70, 20]);
+    let _ = tree[4];
+}
+
+#[test]
+fn can_find_medians() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.median(), None);
+    assert_eq!(tree.median_pair(), None);
+
+    tree.insert(5);
+    assert_eq!(tree.median(), Some(&5));
+    assert_eq!(tree.median_pair(), Some((&5, &5)));
+
+    for value in [1, 9, 3] {
+        tree.insert(value);
+    }
+    assert_eq!(tree.median(), Some(&3));
+    assert_eq!(tree.median_pair(), Some((&3, &5)));
+
+    tree.insert(4);
+    assert_eq!(tree.median(), Some(&4));
+    assert_eq!(tree.median_pair(), Some((&4, &4)));
+}
+
+#[test]
+fn can_rank_keys() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.rank(&5), 0);
+    for value in [50, 30, 70, 20, 40, 60, 80, 40] {
+        tree.insert(value);
+    }
+    assert_eq!(tree.rank(&10), 0);
+    assert_eq!(tree.rank(&40), 2);
+    assert_eq!(tree.rank(&45), 4);
+    assert_eq!(tree.rank(&100), 8);
+    assert_eq!(tree.select(tree.rank(&60)), Some(&60));
+}
+
+#[test]
+fn can_count_items() {
+    let mut tree = BinarySearchTree::new();
+    assert!(tree.is_empty());
+    assert_eq!(tree.len(), 0);
+
+    for value in [5, 2, 8, 2] {
+        tree.insert(value);
+    }
+    assert!(!tree.is_empty());
+    assert_eq!(tree.len(), 4);
+
+    tree.delete(&2);
+    tree.delete(&7);
+    assert_eq!(tree.len(), 3);
+    tree.insert_range(0..10);
+    assert_eq!(tree.len(), 13);
+    tree.drain_range(..5).for_each(drop);
+    assert_eq!(tree.len(), 7);
+    tree.drain().for_each(drop);
+    assert!(tree.is_empty());
+}
+
+#[test]
+fn can_clear() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        tree.clear();
+        for value in [5, 2, 8, 1, 9] {
+            tree.insert(value.to_string());
+        }
+        tree.clear();
+        assert!(tree.is_empty());
+        assert!(!tree.contains("5"));
+
+        tree.insert("3".to_string());
+        assert_eq!(tree.iter_cloned().collect::<Vec<_>>(), vec!["3"]);
+    }
+}
+
+#[test]
+fn remove_hands_back_items() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for value in [50, 30, 70, 20, 40, 60, 80] {
+            tree.insert(vec![value; 3]);
+        }
+        assert_eq!(tree.remove(&vec![50; 3]), Some(vec![50; 3]));
+        assert_eq!(tree.remove(&vec![50; 3]), None);
+        assert_eq!(tree.remove([20, 20, 20].as_slice()), Some(vec![20; 3]));
+        tree.check_invariants();
+        assert_eq!(tree.len(), 5);
+    }
+}
+
+#[test]
+fn can_take_items_by_borrowed_key() {
+    let mut tree = BinarySearchTree::new();
+    for word in ["apple", "banana", "cherry"] {
+        tree.insert(word.to_string());
+    }
+    let taken: Option<String> = tree.take("banana");
+    assert_eq!(taken.as_deref(), Some("banana"));
+    assert_eq!(tree.take("banana"), None);
+    assert_eq!(
+        tree.iter_cloned().collect::<Vec<_>>(),
+        vec!["apple", "cherry"]
+    );
+}
+
+#[test]
+fn replace_swaps_equal_items() {
+    #[derive(Debug)]
+    struct Entry(u32, &'static str);
+
+    impl PartialEq for Entry {
+        fn eq(&self, other: &Self) -> bool {
+            self.0 == other.0
+        }
+    }
+    impl Eq for Entry {}
+    impl PartialOrd for Entry {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    impl Ord for Entry {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    let mut tree = BinarySearchTree::new();
+    assert!(tree.replace(Entry(2, "two")).is_none());
+    assert!(tree.replace(Entry(1, "one")).is_none());
+    let old = tree.replace(Entry(2, "deux")).unwrap();
+    assert_eq!(old.1, "two");
+    assert_eq!(tree.len(), 2);
+    assert_eq!(tree.get(&Entry(2, "")).unwrap().1, "deux");
+}
+
+#[test]
+fn can_get_or_insert_items() {
+    let mut tree = BinarySearchTree::new();
+    tree.insert("apple".to_string());
+
+    let mut made = 0;
+    let item = tree.get_or_insert_with("apple", || {
+        made += 1;
+        "apple".to_string()
+    });
+    assert_eq!(item, "apple");
+    assert_eq!(made, 0);
+
+    let item = tree.get_or_insert_with("banana", || {
+        made += 1;
+        "banana".to_string()
+    });
+    assert_eq!(item, "banana");
+    assert_eq!(made, 1);
+    assert_eq!(tree.len(), 2);
+}
+
+#[test]
+#[should_panic(expected = "new item must be equal to the key")]
+fn get_or_insert_with_checks_new_items() {
+    let mut tree = BinarySearchTree::new();
+    tree.get_or_insert_with("apple", || "banana".to_string());
+}
+
+#[test]
+fn insert_unique_rejects_duplicates() {
+    let mut tree = BinarySearchTree::new();
+    assert!(tree.insert_unique(3));
+    assert!(tree.insert_unique(1));
+    assert!(!tree.insert_unique(3));
+    tree.insert(3);
+    assert!(!tree.insert_unique(3));
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 3, 3]);
+}
+
+#[test]
+fn try_insert_hands_back_rejected_items() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.try_insert(3).copied(), Ok(3));
+    tree.insert(1);
+
+    let error = tree.try_insert(3).unwrap_err();
+    assert_eq!((error.existing, error.value), (&3, 3));
+    assert_eq!(
+        error.to_string(),
+        "failed to insert 3, as 3 is already in the tree"
+    );
+    assert_eq!(tree.len(), 2);
+}
+
+#[test]
+fn can_retain_matching_items() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for value in [5, 2, 8, 1, 9, 3, 7, 4, 6, 4] {
+            tree.insert(value.to_string());
+        }
+        let mut seen = Vec::new();
+        tree.retain(|item| {
+            seen.push(item.clone());
+            item.parse::<i32>().unwrap() % 2 == 0
+        });
+        assert_eq!(seen.len(), 10);
+        tree.check_invariants();
+        assert_eq!(
+            tree.iter_cloned().collect::<Vec<_>>(),
+            vec!["2", "4", "4", "6", "8"]
+        );
+
+        tree.retain(|_| false);
+        assert!(tree.is_empty());
+    }
+}
+
+#[test]
+fn can_split_off_at_a_key() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for value in [5, 2, 8, 1, 9, 3, 7, 4, 6, 4] {
+            tree.insert(value.to_string());
+        }
+        let mut right = tree.split_off("4");
+        tree.check_invariants();
+        right.check_invariants();
+        assert_eq!(tree.iter_cloned().collect::<Vec<_>>(), vec!["1", "2", "3"]);
+        assert_eq!(right.len(), 7);
+        assert_eq!(right.min().map(String::as_str), Some("4"));
+
+        let rest = right.split_off("99");
+        assert_eq!(rest.len(), 0);
+        assert_eq!(right.split_off("0").len(), 7);
+        assert!(right.is_empty());
+    }
+}
+
+#[test]
+fn can_append_trees() {
+    for pooled in [false, true] {
+        let new = || {
+            if pooled {
+                BinarySearchTree::new_pooled()
+            } else {
+                BinarySearchTree::new()
+            }
+        };
+        let mut tree = new();
+        let mut other = new();
+        tree.extend([1, 3, 5]);
+        other.extend([5, 6, 7]);
+        tree.append(&mut other);
+        assert!(other.is_empty());
+        tree.check_invariants();
+        assert_eq!(
+            tree.iter_copied().collect::<Vec<_>>(),
+            vec![1, 3, 5, 5, 6, 7]
+        );
+
+        other.extend([-2, 0]);
+        tree.append(&mut other);
+        tree.check_invariants();
+        assert_eq!(tree.min(), Some(&-2));
+
+        other.extend([2, 4, 4, 8, 1]);
+        tree.append(&mut other);
+        tree.check_invariants();
+        assert_eq!(
+            tree.iter_copied().collect::<Vec<_>>(),
+            vec![-2, 0, 1, 1, 2, 3, 4, 4, 5, 5, 6, 7, 8]
+        );
+
+        tree.append(&mut other);
+        other.append(&mut tree);
+        assert!(tree.is_empty());
+        assert_eq!(other.len(), 13);
+    }
+}
+
+#[test]
+fn can_insert_ranges() {
+    let mut tree = BinarySearchTree::new();
+    for value in [50, 10, 1005, 20, 2000] {
+        tree.insert(value);
+    }
+    tree.insert_range(0..1000);
+    tree.check_invariants();
+
+    let mut expected = (0..1000)
+        .chain([10, 20, 50, 1005, 2000])
+        .collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), expected);
+
+    tree.insert_range(5..5);
+    assert_eq!(tree.iter().count(), expected.len());
+
+    let mut empty = BinarySearchTree::new();
+    empty.insert_range('a'..'e');
+    assert_eq!(empty.iter_copied().collect::<String>(), "abcd");
+}
+
+#[test]
+fn try_from_sorted_vec_reports_first_violation() {
+    use binarysearchtree::{DuplicatePolicy, SortError, SortErrorKind};
+
+    let tree =
+        BinarySearchTree::try_from_sorted_vec(vec![1, 2, 2, 5], DuplicatePolicy::Allow).unwrap();
+    tree.check_invariants();
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 2, 2, 5]);
+
+    let err = BinarySearchTree::try_from_sorted_vec(vec![1, 2, 2, 5], DuplicatePolicy::Reject)
+        .err()
+        .unwrap();
+    assert_eq!(
+        err,
+        SortError {
+            index: 2,
+            kind: SortErrorKind::Duplicate
+        }
+    );
+    assert_eq!(err.to_string(), "item 2 is equal to the item before it");
+
+    let err = BinarySearchTree::try_from_sorted_vec(vec![1, 3, 2, 2], DuplicatePolicy::Allow)
+        .err()
+        .unwrap();
+    assert_eq!(err.index, 2);
+    assert_eq!(err.kind, SortErrorKind::OutOfOrder);
+
+    assert!(BinarySearchTree::<i32>::try_from_sorted_vec(vec![], DuplicatePolicy::Reject).is_ok());
+}
+
+#[test]
+fn can_group_equal_items() {
+    let mut tree = BinarySearchTree::new();
+    for value in [3, 1, 3, 2, 3, 1] {
+        tree.insert(value);
+    }
+    let groups = tree
+        .group_by_equal()
+        .map(|group| group.copied().collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(groups, vec![vec![1, 1], vec![2], vec![3, 3, 3]]);
+    assert_eq!(BinarySearchTree::<i32>::new().group_by_equal().count(), 0);
+}
+
+#[test]
+fn try_for_each_stops_at_first_error() {
+    let tree = BinarySearchTree::from_sorted_iter(1..=10);
+    let mut seen = Vec::new();
+    let result = tree.try_for_each(|&value| {
+        if value > 3 {
+            return Err(value);
+        }
+        seen.push(value);
+        Ok(())
+    });
+    assert_eq!(result, Err(4));
+    assert_eq!(seen, vec![1, 2, 3]);
+
+    let mut sum = 0;
+    assert_eq!(
+        tree.try_for_each(|value| {
+            sum += value;
+            Ok::<_, ()>(())
+        }),
+        Ok(())
+    );
+    assert_eq!(sum, 55);
+}
+
+#[test]
+fn can_fold_over_items() {
+    let tree = BinarySearchTree::from_sorted_iter(1..=10);
+    assert_eq!(tree.fold(0, |sum, value| sum + value), 55);
+
+    let mut seen = Vec::new();
+    tree.for_each(|&value| seen.push(value));
+    assert_eq!(seen, (1..=10).collect::<Vec<_>>());
+
+    let product = tree.try_fold(1u8, |product: u8, &value| {
+        product.checked_mul(value as u8).ok_or(value)
+    });
+    assert_eq!(product, Err(6));
+    assert_eq!(
+        tree.try_fold(0, |sum, value| Ok::<_, ()>(sum + value)),
+        Ok(55)
+    );
+}
+
+#[test]
+fn can_check_ranges_for_items() {
+    let mut tree = BinarySearchTree::new();
+    for value in [10, 5, 20, 15] {
+        tree.insert(value);
+    }
+    assert!(tree.contains_range(12..16));
+    assert!(tree.contains_range(..=5));
+    assert!(tree.contains_range(20..));
+    assert!(!tree.contains_range(6..10));
+    assert!(!tree.contains_range((Bound::Excluded(15), Bound::Excluded(20))));
+    assert!(!tree.contains_range(21..));
+    assert!(!BinarySearchTree::<i32>::new().contains_range(..));
+}
+
+#[test]
+fn compacting_keeps_contents() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for value in 0..200 {
+            tree.insert(value);
+        }
+        for value in (0..200).filter(|value| value % 3 != 0) {
+            tree.delete(&value);
+        }
+        tree.compact();
+        tree.check_invariants();
+        assert_eq!(
+            tree.iter_copied().collect::<Vec<_>>(),
+            (0..200).step_by(3).collect::<Vec<_>>()
+        );
+        tree.insert(1);
+        assert!(tree.contains(&1));
+    }
+    let mut empty = BinarySearchTree::<String>::new();
+    empty.compact();
+    assert!(empty.min().is_none());
+}
+
+#[test]
+fn can_query_key_prefixes() {
+    let mut tree = BinarySearchTree::new();
+    for key in [(2, 30), (1, 10), (3, 5), (2, 10), (1, 20), (2, 20)] {
+        tree.insert(key);
+    }
+    assert_eq!(
+        tree.range_prefix(&2).copied().collect::<Vec<_>>(),
+        vec![(2, 10), (2, 20), (2, 30)]
+    );
+    assert_eq!(tree.range_prefix(&3).count(), 1);
+    assert_eq!(tree.range_prefix(&0).count(), 0);
+    assert_eq!(tree.range_prefix(&4).count(), 0);
+
+    let events = BinarySearchTree::from_sorted_iter([
+        (String::from("acme"), 1, 'a'),
+        (String::from("acme"), 2, 'b'),
+        (String::from("zeta"), 1, 'c'),
+    ]);
+    assert_eq!(
+        events
+            .range_prefix(&String::from("acme"))
+            .map(|event| event.2)
+            .collect::<String>(),
+        "ab"
+    );
+}
+
+#[test]
+fn iterators_can_be_cloned_and_debugged() {
+    let tree = BinarySearchTree::from_sorted_iter([1, 2, 3]);
+    let mut iter = tree.iter();
+    assert_eq!(iter.next(), Some(&1));
+    let rest = iter.clone();
+    assert_eq!(format!("{:?}", rest), "[2, 3]");
+    assert_eq!(iter.collect::<Vec<_>>(), rest.collect::<Vec<_>>());
+}
+
+#[test]
+fn can_consume_into_items() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        for value in ["d", "b", "a", "c", "e"] {
+            tree.insert(value.to_string());
+        }
+        let mut items = Vec::new();
+        for item in tree {
+            items.push(item);
+        }
+        assert_eq!(items, vec!["a", "b", "c", "d", "e"]);
+    }
+
+    let tree = BinarySearchTree::from_sorted_iter((0..10).map(|value| value.to_string()));
+    let mut iter = tree.into_iter();
+    assert_eq!(iter.len(), 10);
+    assert_eq!(iter.next().as_deref(), Some("0"));
+    assert_eq!(iter.len(), 9);
+    drop(iter);
+}
+
+#[test]
+fn can_iterate_in_reverse() {
+    let mut tree = BinarySearchTree::new();
+    for value in [5, 2, 8, 1, 9, 3, 7, 4, 6] {
+        tree.insert(value);
+    }
+    assert_eq!(
+        tree.iter().rev().copied().collect::<Vec<_>>(),
+        (1..=9).rev().collect::<Vec<_>>()
+    );
+
+    let mut iter = tree.iter();
+    assert_eq!(iter.next(), Some(&1));
+    assert_eq!(iter.next_back(), Some(&9));
+    assert_eq!(iter.len(), 7);
+    let middle = iter.by_ref().skip(3).copied().collect::<Vec<_>>();
+    assert_eq!(middle, vec![5, 6, 7, 8]);
+    assert_eq!(iter.next_back(), None);
+
+    let chunks = tree.chunks(2);
+    assert_eq!(
+        chunks[1].clone().rev().copied().collect::<Vec<_>>(),
+        vec![9, 8, 7, 6, 5]
+    );
+
+    let mut items = BinarySearchTree::from_sorted_iter([1, 1, 2, 2, 2, 3]).into_iter();
+    assert_eq!(items.next_back(), Some(3));
+    assert_eq!(items.next(), Some(1));
+    assert_eq!(items.rev().collect::<Vec<_>>(), vec![2, 2, 2, 1]);
+
+    let pairs = BinarySearchTree::from_sorted_iter([(1, 'a'), (2, 'b'), (2, 'c'), (3, 'd')]);
+    assert_eq!(
+        pairs
+            .range_prefix(&2)
+            .rev()
+            .map(|pair| pair.1)
+            .collect::<String>(),
+        "cb"
+    );
+
+    let equal = BinarySearchTree::from_sorted_iter([1, 2, 2, 3]);
+    let runs = equal
+        .group_by_equal()
+        .map(|run| run.rev().len())
+        .collect::<Vec<_>>();
+    assert_eq!(runs, vec![1, 2, 1]);
+
+    let mut drained = tree.drain_range(3..7).rev();
+    assert_eq!(drained.next(), Some(6));
+    assert_eq!(drained.next(), Some(5));
+    drop(drained);
+    assert_eq!(tree.iter().len(), 5);
+}
+
+#[test]
+fn can_iterate_level_by_level() {
+    let mut tree = BinarySearchTree::new();
+    for value in [5, 2, 8, 1, 9, 3, 4] {
+        tree.insert(value);
+    }
+    assert_eq!(
+        tree.level_order().copied().collect::<Vec<_>>(),
+        vec![5, 2, 8, 1, 3, 9, 4]
+    );
+    let levels = tree.level_order().with_levels();
+    assert_eq!(levels.size_hint(), (7, Some(7)));
+    assert_eq!(
+        levels
+            .map(|(level, &item)| (level, item))
+            .collect::<Vec<_>>(),
+        vec![(0, 5), (1, 2), (1, 8), (2, 1), (2, 3), (2, 9), (3, 4)]
+    );
+    assert_eq!(BinarySearchTree::<i32>::new().level_order().count(), 0);
+}
+
+#[test]
+fn can_iterate_with_depths() {
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.iter_with_depth().next(), None);
+
+    for value in [5, 2, 8, 1, 3, 9, 4] {
+        tree.insert(value);
+    }
+    // 5
+    // 2 8
+    // 1 3 9
+    // 4
+    assert_eq!(
+        tree.iter_with_depth().collect::<Vec<_>>(),
+        vec![
+            (2, &1),
+            (1, &2),
+            (2, &3),
+            (3, &4),
+            (0, &5),
+            (1, &8),
+            (2, &9)
+        ]
+    );
+    assert_eq!(tree.iter_with_depth().len(), 7);
+}
+
+#[test]
+fn can_iterate_in_pre_and_post_order() {
+    let mut tree = BinarySearchTree::new();
+    for value in [5, 2, 8, 1, 9, 3, 4] {
+        tree.insert(value);
+    }
+    assert_eq!(
+        tree.pre_order().copied().collect::<Vec<_>>(),
+        vec![5, 2, 1, 3, 4, 8, 9]
+    );
+    assert_eq!(
+        tree.post_order().copied().collect::<Vec<_>>(),
+        vec![1, 4, 3, 2, 9, 8, 5]
+    );
+    assert_eq!(tree.post_order().len(), 7);
+
+    let mut rebuilt = BinarySearchTree::new();
+    for &value in tree.pre_order() {
+        rebuilt.insert(value);
+    }
+    assert_eq!(
+        rebuilt.level_order().collect::<Vec<_>>(),
+        tree.level_order().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn can_iterate_in_zigzag_order() {
+    let tree = BinarySearchTree::from_sorted_iter(1..=10);
+    // 8
+    // 4 10
+    // 2 6 9
+    // 1 3 5 7
+    assert_eq!(
+        tree.level_order().copied().collect::<Vec<_>>(),
+        vec![8, 4, 10, 2, 6, 9, 1, 3, 5, 7]
+    );
+    assert_eq!(
+        tree.zigzag().copied().collect::<Vec<_>>(),
+        vec![8, 10, 4, 2, 6, 9, 7, 5, 3, 1]
+    );
+    assert_eq!(tree.zigzag().len(), 10);
+    assert_eq!(BinarySearchTree::<i32>::new().zigzag().next(), None);
+}
+
+#[test]
+fn can_move_a_tree_to_another_thread() {
+    for mut tree in [BinarySearchTree::new(), BinarySearchTree::new_pooled()] {
+        tree.extend(["3", "44", "5", "1"].map(String::from));
+        let handle = thread::spawn(move || {
+            tree.insert(String::from("2"));
+            tree
+        });
+        let tree = handle.join().unwrap();
+        assert_eq!(
+            tree.iter().map(String::as_str).collect::<Vec<_>>(),
+            vec!["1", "2", "3", "44", "5"]
+        );
+
+        let items: Vec<String> = thread::spawn(move || tree.into_iter().collect())
+            .join()
+            .unwrap();
+        assert_eq!(items.len(), 5);
+    }
+}
+
+#[test]
+fn can_share_a_tree_between_threads() {
+    let tree: BinarySearchTree<i32> = (0..100).collect();
+    let (sum, max) = thread::scope(|scope| {
+        let sum = scope.spawn(|| tree.iter().sum::<i32>());
+        let max = scope.spawn(|| {
+            let mut cursor = tree.cursor_back();
+            cursor.move_prev();
+            cursor.peek_next().copied()
+        });
+        (sum.join().unwrap(), max.join().unwrap())
+    });
+    assert_eq!(sum, 4950);
+    assert_eq!(max, Some(99));
+
+    let iter = tree.iter();
+    let rest: Vec<i32> = thread::scope(|scope| {
+        scope
+            .spawn(|| iter.skip(97).copied().collect())
+            .join()
+            .unwrap()
+    });
+    assert_eq!(rest, vec![97, 98, 99]);
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn can_build_arbitrary_trees() {
+    use arbitrary::{Arbitrary, Unstructured};
+
+    let bytes: Vec<u8> = (0..4096u32)
+        .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+        .collect();
+    let mut u = Unstructured::new(&bytes);
+    let mut heights = Vec::new();
+    while !u.is_empty() {
+        let tree = BinarySearchTree::<u8>::arbitrary(&mut u).unwrap();
+        tree.check_invariants();
+        assert!(tree.iter().is_sorted());
+        heights.push(tree.height());
+    }
+    assert!(heights.len() > 1);
+    assert!(heights.iter().any(|&height| height > 1));
+}
+
+#[cfg(feature = "proptest")]
+#[test]
+fn can_generate_trees_with_proptest() {
+    use binarysearchtree::strategies::{trees, TreeParams};
+    use proptest::strategy::{Strategy, ValueTree};
+    use proptest::test_runner::TestRunner;
+
+    let mut runner = TestRunner::deterministic();
+    let mut generate = |params: TreeParams| {
+        trees(0..1000, params)
+            .new_tree(&mut runner)
+            .unwrap()
+            .current()
+    };
+
+    let tree = generate(TreeParams {
+        size: 50.into(),
+        ..TreeParams::default()
+    });
+    tree.check_invariants();
+    assert_eq!(tree.len(), 50);
+    assert_eq!(tree.height(), 6);
+
+    let vine = generate(TreeParams {
+        size: 50.into(),
+        skew: 1.0,
+        ..TreeParams::default()
+    });
+    assert_eq!(vine.height(), 50);
+
+    let same = generate(TreeParams {
+        size: 50.into(),
+        duplicates: 1.0,
+        ..TreeParams::default()
+    });
+    assert_eq!(same.len(), 50);
+    assert_eq!(same.min(), same.max());
+}
+
+#[test]
+fn can_use_a_map() {
+    for mut map in [BstMap::new(), BstMap::new_pooled()] {
+        assert_eq!(map.insert(String::from("b"), 2), None);
+        assert_eq!(map.insert(String::from("a"), 1), None);
+        assert_eq!(map.insert(String::from("c"), 3), None);
+        assert_eq!(map.insert(String::from("b"), 20), Some(2));
+        assert_eq!(map.len(), 3);
+
+        assert_eq!(map.get("b"), Some(&20));
+        assert_eq!(map.get("d"), None);
+        *map.get_mut("a").unwrap() += 10;
+        assert!(map.contains_key("a"));
+
+        assert_eq!(map.remove("c"), Some(3));
+        assert_eq!(map.remove("c"), None);
+
+        let pairs: Vec<(&str, i32)> = map.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        assert_eq!(pairs, vec![("a", 11), ("b", 20)]);
+        assert_eq!(format!("{map:?}"), r#"{"a": 11, "b": 20}"#);
+    }
+
+    let map: BstMap<i32, char> = [(2, 'x'), (1, 'a'), (2, 'b')].into_iter().collect();
+    assert_eq!(
+        (&map).into_iter().rev().collect::<Vec<_>>(),
+        vec![(&2, &'b'), (&1, &'a')]
+    );
+}
+
+#[test]
+fn can_update_a_map_through_entries() {
+    let mut counts = BstMap::new();
+    for word in "the cat sat on the mat the end".split(' ') {
+        *counts.entry(word).or_insert(0) += 1;
+    }
+    assert_eq!(counts.get("the"), Some(&3));
+    assert_eq!(counts.get("cat"), Some(&1));
+    assert_eq!(counts.len(), 6);
+
+    counts.entry("cat").and_modify(|n| *n += 10).or_default();
+    counts.entry("dog").and_modify(|n| *n += 10).or_default();
+    assert_eq!(counts.get("cat"), Some(&11));
+    assert_eq!(counts.get("dog"), Some(&0));
+    assert_eq!(*counts.entry("cow").or_insert_with(|| 7), 7);
+
+    match counts.entry("on") {
+        Entry::Occupied(entry) => {
+            assert_eq!(entry.key(), &"on");
+            assert_eq!(entry.remove(), 1);
+        }
+        Entry::Vacant(_) => panic!("\"on\" should be in the map"),
+    }
+    match counts.entry("on") {
+        Entry::Occupied(_) => panic!("\"on\" should be gone"),
+        Entry::Vacant(entry) => assert_eq!(entry.into_key(), "on"),
+    }
+
+    let keys: Vec<&str> = counts.iter().map(|(k, _)| *k).collect();
+    assert_eq!(keys, vec!["cat", "cow", "dog", "end", "mat", "sat", "the"]);
+}
+
+#[test]
+fn can_get_and_remove_whole_map_entries() {
+    let mut map: BstMap<String, i32> = [("a", 1), ("b", 2)]
+        .into_iter()
+        .map(|(k, v)| (String::from(k), v))
+        .collect();
+
+    let (key, value) = map.get_key_value("b").unwrap();
+    assert_eq!((key.as_str(), *value), ("b", 2));
+    assert_eq!(map.get_key_value("c"), None);
+
+    assert_eq!(map.remove_entry("a"), Some((String::from("a"), 1)));
+    assert_eq!(map.remove_entry("a"), None);
+    match map.entry(String::from("b")) {
+        Entry::Occupied(entry) => assert_eq!(entry.remove_entry(), (String::from("b"), 2)),
+        Entry::Vacant(_) => panic!("\"b\" should be in the map"),
+    }
+    assert!(map.is_empty());
+}
+
+#[test]
+fn can_iterate_over_map_keys_and_values() {
+    let mut map: BstMap<i32, String> = (1..=5).map(|i| (i, i.to_string())).collect();
+    assert_eq!(map.keys().copied().collect::<Vec<_>>(), vec![1, 2, 3, 4, 5]);
+    assert_eq!(map.values().next_back(), Some(&String::from("5")));
+    assert_eq!(map.values().len(), 5);
+
+    let mut values = map.values_mut();
+    let first = values.next().unwrap();
+    let last = values.next_back().unwrap();
+    first.push('!');
+    last.push('?');
+    for value in values {
+        value.insert(0, '-');
+    }
+    assert_eq!(
+        map.values().cloned().collect::<Vec<_>>(),
+        vec!["1!", "-2", "-3", "-4", "5?"]
+    );
+}
+
+#[test]
+fn can_count_items_in_a_multiset() {
+    let mut set: BstMultiSet<char> = "mississippi".chars().collect();
+    assert_eq!(set.len(), 11);
+    assert_eq!(set.distinct_len(), 4);
+    assert_eq!(set.count(&'s'), 4);
+    assert_eq!(set.count(&'z'), 0);
+
+    set.insert_n('z', 2);
+    set.insert_n('y', 0);
+    assert!(!set.contains(&'y'));
+    assert!(set.remove_one(&'z'));
+    assert!(set.remove_one(&'z'));
+    assert!(!set.remove_one(&'z'));
+    assert_eq!(set.remove_all(&'s'), 4);
+    assert_eq!(set.remove_all(&'s'), 0);
+
+    assert_eq!(set.iter().collect::<String>(), "iiiimpp");
+    assert_eq!(set.iter().rev().collect::<String>(), "ppmiiii");
+    assert_eq!(set.iter().len(), 7);
+    assert_eq!(
+        set.counts().map(|(c, n)| (*c, *n)).collect::<Vec<_>>(),
+        vec![('i', 4), ('m', 1), ('p', 2)]
+    );
+
+    let mut iter = set.iter();
+    assert_eq!(iter.next(), Some(&'i'));
+    assert_eq!(iter.next_back(), Some(&'p'));
+    assert_eq!(iter.next_back(), Some(&'p'));
+    assert_eq!(iter.next_back(), Some(&'m'));
+    assert_eq!(iter.next_back(), Some(&'i'));
+    assert_eq!(iter.collect::<String>(), "ii");
+}
+
+#[test]
+fn can_choose_what_happens_to_duplicates() {
+    use binarysearchtree::DuplicatePolicy;
+    use std::cmp::Ordering;
+
+    let mut tree = BinarySearchTree::new();
+    assert_eq!(tree.duplicate_policy(), DuplicatePolicy::Allow);
+    assert_eq!(tree.insert(1), None);
+    assert_eq!(tree.insert(1), None);
+    assert_eq!(tree.len(), 2);
+
+    let mut tree = BinarySearchTree::new_pooled().with_duplicates(DuplicatePolicy::Reject);
+    assert_eq!(tree.insert(String::from("a")), None);
+    assert_eq!(tree.insert(String::from("a")), Some(String::from("a")));
+    tree.extend(["a", "b", "b"].map(String::from));
+    assert_eq!(tree.len(), 2);
+    assert_eq!(tree.clone().duplicate_policy(), DuplicatePolicy::Reject);
+    assert_eq!(
+        tree.split_off("b").duplicate_policy(),
+        DuplicatePolicy::Reject
+    );
+
+    // Ordered by key alone, so we can tell which of two equal items is in the tree.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Tagged(i32, char);
+    impl PartialOrd for Tagged {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    impl Ord for Tagged {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+
+    let mut tree = BinarySearchTree::new().with_duplicates(DuplicatePolicy::Replace);
+    assert_eq!(tree.insert(Tagged(1, 'a')), None);
+    assert_eq!(tree.insert(Tagged(1, 'b')).map(|old| old.1), Some('a'));
+    assert_eq!(tree.len(), 1);
+    assert_eq!(tree.min().map(|item| item.1), Some('b'));
+
+    let tree = BinarySearchTree::try_from_sorted_vec(
+        vec![Tagged(1, 'a'), Tagged(1, 'b'), Tagged(2, 'c')],
+        DuplicatePolicy::Replace,
+    )
+    .unwrap();
+    assert_eq!(tree.iter().map(|item| item.1).collect::<String>(), "bc");
+    assert_eq!(tree.duplicate_policy(), DuplicatePolicy::Replace);
+}
+
+#[test]
+fn cloned_trees_keep_the_duplicate_policy() {
+    use binarysearchtree::DuplicatePolicy;
+
+    let rejecting = BinarySearchTree::from_iter([1, 2, 3]).with_duplicates(DuplicatePolicy::Reject);
+    let allowing = BinarySearchTree::from_iter([1, 1, 2]);
+
+    let mut copy = BinarySearchTree::new();
+    copy.clone_from(&rejecting);
+    assert_eq!(copy.duplicate_policy(), DuplicatePolicy::Reject);
+    assert_eq!(copy.insert(2), Some(2));
+
+    let mut copy = rejecting.clone();
+    copy.clone_from(&allowing);
+    assert_eq!(copy.duplicate_policy(), DuplicatePolicy::Allow);
+    assert_eq!(copy, allowing);
+    copy.check_invariants();
+
+    let range = rejecting.clone_range(2..);
+    assert_eq!(range.duplicate_policy(), DuplicatePolicy::Reject);
+    assert_eq!(range.iter_copied().collect::<Vec<_>>(), vec![2, 3]);
+}
+
+#[test]
+fn can_find_intervals_by_point_and_range() {
+    let mut tree = IntervalTree::new();
+    tree.insert(10..20, "a");
+    tree.insert(5..12, "b");
+    tree.insert(15..30, "c");
+    tree.insert(40..50, "d");
+    tree.insert(15..30, "e");
+    tree.insert(7..7, "empty");
+    assert_eq!(tree.len(), 6);
+
+    fn values<'a>(found: Vec<(&std::ops::Range<i32>, &&'a str)>) -> Vec<&'a str> {
+        found.into_iter().map(|(_, value)| *value).collect()
+    }
+
+    assert_eq!(values(tree.containing(&11)), vec!["b", "a"]);
+    assert_eq!(values(tree.containing(&20)), vec!["c", "e"]);
+    assert_eq!(values(tree.containing(&7)), vec!["b"]);
+    assert_eq!(values(tree.containing(&35)), Vec::<&str>::new());
+    assert_eq!(values(tree.overlapping(&(29..41))), vec!["c", "e", "d"]);
+    assert_eq!(values(tree.overlapping(&(30..40))), Vec::<&str>::new());
+    assert_eq!(values(tree.overlapping(&(12..12))), Vec::<&str>::new());
+
+    assert_eq!(tree.remove(&(15..30)), Some("c"));
+    assert_eq!(tree.remove(&(15..31)), None);
+    assert_eq!(tree.remove(&(10..20)), Some("a"));
+    assert_eq!(values(tree.containing(&11)), vec!["b"]);
+    assert_eq!(values(tree.containing(&25)), vec!["e"]);
+}
+
+#[test]
+fn interval_queries_match_a_linear_scan() {
+    // A small linear congruential generator, so the test doesn't depend on `rand`.
+    let mut seed = 12345u64;
+    let mut next = move |bound: u64| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) % bound
+    };
+
+    let mut tree = IntervalTree::new();
+    let mut all = Vec::new();
+    for i in 0..300 {
+        let start = next(1000);
+        let interval = start..start + next(100);
+        tree.insert(interval.clone(), i);
+        all.push((interval, i));
+        if next(4) == 0 {
+            // Equal intervals are removed oldest first, and `all` is oldest first too.
+            let interval = all[next(all.len() as u64) as usize].0.clone();
+            let oldest = all
+                .iter()
+                .position(|(other, _)| *other == interval)
+                .unwrap();
+            assert_eq!(tree.remove(&interval), Some(all.remove(oldest).1));
+        }
+    }
+    assert_eq!(tree.len(), all.len());
+
+    for _ in 0..200 {
+        let point = next(1100);
+        let mut expected: Vec<i32> = all
+            .iter()
+            .filter(|(interval, _)| interval.contains(&point))
+            .map(|(_, value)| *value)
+            .collect();
+        let mut found: Vec<i32> = tree
+            .containing(&point)
+            .into_iter()
+            .map(|(_, v)| *v)
+            .collect();
+        expected.sort();
+        found.sort();
+        assert_eq!(found, expected);
+
+        let start = next(1100);
+        let range = start..start + next(50);
+        let mut expected: Vec<i32> = all
+            .iter()
+            .filter(|(interval, _)| {
+                interval.start < interval.end
+                    && range.start < range.end
+                    && interval.start < range.end
+                    && range.start < interval.end
+            })
+            .map(|(_, value)| *value)
+            .collect();
+        let mut found: Vec<i32> = tree
+            .overlapping(&range)
+            .into_iter()
+            .map(|(_, v)| *v)
+            .collect();
+        expected.sort();
+        found.sort();
+        assert_eq!(found, expected);
+    }
+}
+
+#[test]
+fn gaps_and_insert_range_work_with_any_successor() {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+    struct Even(u32);
+
+    impl Successor for Even {
+        fn successor(&self) -> Option<Self> {
+            self.0.checked_add(2).map(Even)
+        }
+    }
+
+    let mut tree = BinarySearchTree::new();
+    tree.insert_range(Even(0)..Even(7));
+    assert_eq!(
+        tree.iter_copied().collect::<Vec<_>>(),
+        vec![Even(0), Even(2), Even(4), Even(6)]
+    );
+
+    tree.delete(&Even(2));
+    let gaps: Vec<_> = tree.gaps(Even(0)..Even(10)).collect();
+    assert_eq!(gaps, vec![Even(2)..Even(4), Even(8)..Even(10)]);
+
+    let mut chars = BinarySearchTree::new();
+    chars.insert_range('\u{D7FE}'..'\u{E001}');
+    assert_eq!(
+        chars.iter_copied().collect::<Vec<_>>(),
+        vec!['\u{D7FE}', '\u{D7FF}', '\u{E000}']
+    );
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn can_update_map_values_in_parallel() {
+    use rayon::prelude::*;
+
+    let mut map: BstMap<u64, u64> = (0..2_000).map(|key| (key, key)).collect();
+    map.par_values_mut().for_each(|value| *value *= 3);
+    assert!(map.iter().all(|(key, value)| *value == key * 3));
+
+    map.par_iter_mut().for_each(|(key, value)| *value += key);
+    assert!(map.iter().all(|(key, value)| *value == key * 4));
+
+    assert_eq!(map.par_iter_mut().len(), 2_000);
+    let keys: Vec<u64> = (&mut map).into_par_iter().map(|(key, _)| *key).collect();
+    assert_eq!(keys, (0..2_000).collect::<Vec<_>>());
+
+    for (key, value) in map.iter_mut().rev().take(2) {
+        *value = *key;
+    }
+    assert_eq!(map.get(&1_999), Some(&1_999));
+    assert_eq!(map.get(&1_997), Some(&7_988));
+
+    let mut empty: BstMap<u64, u64> = BstMap::new();
+    assert_eq!(empty.par_values_mut().count(), 0);
+}
+
+#[test]
+fn get_or_insert_with_compares_along_one_path() {
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    static COMPARISONS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Counted(i32);
+
+    impl PartialOrd for Counted {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Counted {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            COMPARISONS.fetch_add(1, Relaxed);
+            self.0.cmp(&other.0)
+        }
+    }
+
+    let mut tree = BinarySearchTree::new();
+    for i in [4, 2, 6, 1, 3, 5, 7] {
+        tree.insert(Counted(i));
+    }
+
+    COMPARISONS.store(0, Relaxed);
+    tree.get_or_insert_with(&Counted(8), || Counted(8));
+    assert_eq!(COMPARISONS.load(Relaxed), 3);
+    tree.check_invariants();
+    assert_eq!(
+        tree.iter().map(|item| item.0).collect::<Vec<_>>(),
+        (1..=8).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn borrowing_iterators_are_send_and_sync() {
+    use binarysearchtree::{
+        Cursor, DrainRange, ExtractIf, Gaps, GroupByEqual, LevelOrder, MapIterMut, OccupiedEntry,
+        Pairs, PostOrder, PreOrder, Strided, TopK, ValuesMut, WithDepth, WithLevels, Zigzag,
+    };
+    use std::cell::Cell;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+    fn assert_send<T: Send>() {}
+
+    assert_send_sync::<BinarySearchTree<i32>>();
+    assert_send_sync::<binarysearchtree::Iter<'static, i32>>();
+    assert_send_sync::<Cursor<'static, i32>>();
+    assert_send_sync::<LevelOrder<'static, i32>>();
+    assert_send_sync::<WithLevels<'static, i32>>();
+    assert_send_sync::<Zigzag<'static, i32>>();
+    assert_send_sync::<WithDepth<'static, i32>>();
+    assert_send_sync::<PreOrder<'static, i32>>();
+    assert_send_sync::<PostOrder<'static, i32>>();
+    assert_send_sync::<GroupByEqual<'static, i32>>();
+    assert_send_sync::<TopK<'static, i32>>();
+    assert_send_sync::<Strided<'static, i32>>();
+    assert_send_sync::<Pairs<'static, i32>>();
+    assert_send_sync::<Gaps<'static, i32>>();
+    assert_send_sync::<DrainRange<'static, i32>>();
+    assert_send_sync::<ExtractIf<'static, i32, fn(&i32) -> bool>>();
+    assert_send_sync::<MapIterMut<'static, i32, i32>>();
+    assert_send_sync::<ValuesMut<'static, i32, i32>>();
+    assert_send_sync::<OccupiedEntry<'static, i32, i32>>();
+    #[cfg(feature = "rand")]
+    assert_send_sync::<binarysearchtree::Shuffled<'static, 'static, i32, rand::rngs::StdRng>>();
+
+    // Draining moves items out, so it only needs them to be sendable, not shareable.
+    assert_send::<DrainRange<'static, Cell<i32>>>();
+    assert_send::<BinarySearchTree<Cell<i32>>>();
+}
+
+#[test]
+fn map_insert_compares_along_one_path() {
+    use std::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+    static COMPARISONS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Counted(i32);
+
+    impl PartialOrd for Counted {
+        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Counted {
+        fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+            COMPARISONS.fetch_add(1, Relaxed);
+            self.0.cmp(&other.0)
+        }
+    }
+
+    let mut map = BstMap::new();
+    for key in [4, 2, 6, 1, 3, 5, 7] {
+        map.insert(Counted(key), key);
+    }
+
+    COMPARISONS.store(0, Relaxed);
+    assert_eq!(map.insert(Counted(8), 8), None);
+    assert_eq!(COMPARISONS.load(Relaxed), 3);
+
+    COMPARISONS.store(0, Relaxed);
+    assert_eq!(map.insert(Counted(6), 60), Some(6));
+    assert_eq!(COMPARISONS.load(Relaxed), 2);
+
+    assert_eq!(
+        map.iter()
+            .map(|(key, value)| (key.0, *value))
+            .collect::<Vec<_>>(),
+        vec![
+            (1, 1),
+            (2, 2),
+            (3, 3),
+            (4, 4),
+            (5, 5),
+            (6, 60),
+            (7, 7),
+            (8, 8)
+        ]
+    );
+}
+
+#[test]
+fn duplicate_policy_holds_for_bulk_inserts() {
+    use binarysearchtree::DuplicatePolicy;
+    use std::cmp::Ordering;
+
+    fn strictly_sorted<T: Ord>(tree: &BinarySearchTree<T>) -> bool {
+        tree.iter_pairs().all(|(a, b)| a < b)
+    }
+
+    let reject = |items: &[i32]| {
+        BinarySearchTree::from_iter(items.iter().copied()).with_duplicates(DuplicatePolicy::Reject)
+    };
+    let allow = |items: &[i32]| BinarySearchTree::from_iter(items.iter().copied());
+
+    // Choosing a policy drops the duplicates already there.
+    let tree = reject(&[1, 1, 2, 3, 3, 3]);
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    tree.check_invariants();
+
+    let mut tree = reject(&[1, 3, 5]);
+    tree.append(&mut allow(&[3, 4, 4, 5, 6]));
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 3, 4, 5, 6]);
+    tree.check_invariants();
+
+    // Even when the trees only touch at their ends.
+    let mut tree = reject(&[1, 2]);
+    tree.append(&mut allow(&[2, 3, 3]));
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    let mut tree = reject(&[]);
+    tree.append(&mut allow(&[7, 7]));
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![7]);
+
+    let mut tree = reject(&[2, 4, 20]);
+    tree.insert_range(0..10);
+    assert_eq!(
+        tree.iter_copied().collect::<Vec<_>>(),
+        (0..10).chain([20]).collect::<Vec<_>>()
+    );
+    tree.check_invariants();
+
+    let mut tree = reject(&[1, 3]);
+    let mut cursor = tree.cursor_front_mut();
+    cursor.move_next();
+    assert_eq!(cursor.insert_before(1), Err(1));
+    assert_eq!(cursor.insert_after(3), Err(3));
+    assert_eq!(cursor.insert_before(2), Ok(()));
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+
+    let mut source = allow(&[4, 5, 5, 6, 9]);
+    let mut tree = reject(&[1, 6]);
+    assert_eq!(source.splice_range(5..7, &mut tree), 3);
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 5, 6]);
+    let mut tree = reject(&[1, 2]);
+    source.splice_range(.., &mut tree);
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 2, 4, 9]);
+    tree.check_invariants();
+
+    let tree = BinarySearchTree::join(reject(&[1, 2]), 2, allow(&[2, 3, 3]));
+    assert_eq!(tree.iter_copied().collect::<Vec<_>>(), vec![1, 2, 3]);
+    tree.check_invariants();
+    let tree = BinarySearchTree::join(reject(&[1]), 2, reject(&[3]));
+    assert!(strictly_sorted(&tree));
+    assert_eq!(tree.len(), 3);
+
+    // With `Replace`, the item added last wins.
+    #[derive(Debug, PartialEq, Eq)]
+    struct Tagged(i32, char);
+    impl PartialOrd for Tagged {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+    impl Ord for Tagged {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.0.cmp(&other.0)
+        }
+    }
+    let tags = |tree: &BinarySearchTree<Tagged>| tree.iter().map(|item| item.1).collect::<String>();
+
+    let mut tree = BinarySearchTree::from_iter([Tagged(1, 'a'), Tagged(1, 'b'), Tagged(2, 'c')])
+        .with_duplicates(DuplicatePolicy::Replace);
+    assert_eq!(tags(&tree), "bc");
+
+    let mut other = BinarySearchTree::from_iter([Tagged(2, 'd'), Tagged(2, 'e'), Tagged(3, 'f')]);
+    tree.append(&mut other);
+    assert_eq!(tags(&tree), "bef");
+
+    let mut other = BinarySearchTree::from_iter([Tagged(1, 'g'), Tagged(5, 'h')]);
+    other.splice_range(.., &mut tree);
+    assert_eq!(tags(&tree), "gefh");
+    assert!(strictly_sorted(&tree));
+    tree.check_invariants();
+}
+
+#[test]
+fn removing_intervals_keeps_largest_ends_up_to_date() {
+    // Removing a leaf has to shrink the largest end known by its parent and the root.
+    let mut tree = IntervalTree::new();
+    tree.insert(10..11, 'a');
+    tree.insert(5..6, 'p');
+    tree.insert(7..100, 'd');
+    assert_eq!(tree.remove(&(7..100)), Some('d'));
+    tree.check_invariants();
+    assert!(tree.overlapping(&(50..60)).is_empty());
+
+    // A small linear congruential generator, so the test doesn't depend on `rand`.
+    let mut seed = 54321u64;
+    let mut next = move |bound: u64| {
+        seed = seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (seed >> 33) % bound
+    };
+
+    let mut tree = IntervalTree::new();
+    let mut all = Vec::new();
+    for i in 0..500 {
+        let start = next(1000);
+        let interval = start..start + next(200);
+        tree.insert(interval.clone(), i);
+        all.push(interval);
+        if next(2) == 0 {
+            let interval = all.swap_remove(next(all.len() as u64) as usize);
+            assert!(tree.remove(&interval).is_some());
+            tree.check_invariants();
+        }
+    }
+    while let Some(interval) = all.pop() {
+        assert!(tree.remove(&interval).is_some());
+        tree.check_invariants();
+    }
+    assert!(tree.is_empty());
+}